@@ -1,148 +1,485 @@
-use crate::ast::{BinaryOp, Expr, UnaryOp};
-use crate::error::LoxResult;
+use std::cell::{Cell, RefCell};
+use std::io::{self, BufRead, BufReader, Write};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ast::{BinaryOp, Expr, ExprKind, LogicalOp, Stmt, StmtKind, UnaryOp};
+use crate::error::{LoxResult, RuntimeError};
 use crate::interpreter::LoxInterpreter;
 use crate::lexer::scan_tokens;
-use crate::parser::parse_tokens;
-use crate::token::Token;
-use crate::types::LoxType;
-use std::collections::VecDeque;
-use crate::stack::Frame;
+use crate::lists;
+use crate::maps;
+use crate::math::Rng;
+use crate::native::{self, Arity, Context, Host, NativeFunction, NativeObject};
+use crate::parser::{parse_program, parse_tokens};
+use crate::prelude;
+use crate::stack::{self, Frame};
+use crate::strings;
+use crate::system::Capabilities;
+use crate::token::{Span, Token};
+use crate::types::{LoxMap, LoxType, MapKey};
 use cons_list::ConsList;
 
+/// How many Lox calls may be nested before reporting a stack overflow,
+/// unless changed with `set_max_call_depth`.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 5_000;
+
+/// A thread stack big enough for `DEFAULT_MAX_CALL_DEPTH` nested calls. A
+/// call takes a few KiB of Rust stack in a release build but up to 100KiB in
+/// a debug build.
+pub const STACK_SIZE: usize = if cfg!(debug_assertions) {
+    1 << 30
+} else {
+    64 << 20
+};
+
+/// How a statement finished, unwinding enclosing statements until handled.
+enum Flow {
+    Normal,
+    Return(LoxType),
+    Break,
+    Continue,
+}
+
 pub struct AstIntepreter<'a> {
     lexer: &'a dyn Fn(&str) -> LoxResult<Vec<Token>>,
     parser: &'a dyn Fn(&[Token]) -> LoxResult<Expr>,
+    program_parser: &'a dyn Fn(&[Token]) -> LoxResult<Vec<Stmt>>,
+    /// Call stack, with the global and builtin frames at the bottom. Persists across calls
+    /// to `eval` until `reset`.
+    stack: ConsList<Frame>,
+    /// How many Lox function calls are in progress.
+    depth: Cell<usize>,
+    max_call_depth: usize,
+    /// Where `print` writes to, stdout unless replaced with `set_output`.
+    output: RefCell<Box<dyn Write + 'a>>,
+    /// Where `input()` reads from, stdin unless replaced with `set_input`.
+    input: RefCell<Box<dyn BufRead + 'a>>,
+    /// Behind `random()`, seeded from the clock unless `seed_random` is used.
+    rng: RefCell<Rng>,
+    capabilities: Capabilities,
+    /// What `args()` returns.
+    args: Vec<String>,
 }
 
 impl<'a> AstIntepreter<'a> {
     pub fn new(
         lexer: &'a dyn Fn(&str) -> LoxResult<Vec<Token>>,
         parser: &'a dyn Fn(&[Token]) -> LoxResult<Expr>,
+        program_parser: &'a dyn Fn(&[Token]) -> LoxResult<Vec<Stmt>>,
     ) -> AstIntepreter<'a> {
-        AstIntepreter { lexer, parser }
-    }
-
-    fn eval_rec(&self, ast: &Expr, stack: &ConsList<Frame>) -> LoxResult<Expr> {
-        Ok({
-            match ast {
-                lit @ Expr::Literal(_) => lit.clone(),
-                Expr::Unary(op, expr) => {
-                    let val = self.eval_rec(&**expr, stack)?;
-                    match op {
-                        UnaryOp::Bang => match val {
-                            Expr::Literal(LoxType::Boolean(b)) => {
-                                Expr::Literal(LoxType::Boolean(!b))
-                            }
-                            _ => panic!("Unary operator '!' can only be used on booleans"),
-                        },
-                        UnaryOp::Minus => match val {
-                            Expr::Literal(LoxType::Number(num)) => {
-                                Expr::Literal(LoxType::Number(-num))
-                            }
-                            _ => panic!("Unary operator '!' can only be used on booleans"),
-                        },
+        AstIntepreter {
+            lexer,
+            parser,
+            program_parser,
+            stack: global_stack(),
+            depth: Cell::new(0),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            output: RefCell::new(Box::new(io::stdout())),
+            input: RefCell::new(Box::new(BufReader::new(io::stdin()))),
+            rng: RefCell::new(Rng::new(clock_seed())),
+            capabilities: Capabilities::default(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn set_output(&mut self, output: impl Write + 'a) {
+        self.output = RefCell::new(Box::new(output));
+    }
+
+    pub fn set_input(&mut self, input: impl BufRead + 'a) {
+        self.input = RefCell::new(Box::new(input));
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    /// Makes `random()` produce the same sequence on every run with `seed`.
+    pub fn seed_random(&self, seed: u64) {
+        *self.rng.borrow_mut() = Rng::new(seed);
+    }
+
+    /// The frame holding global variables. Builtins live in a frame below it
+    /// so they can be shadowed.
+    pub fn globals(&self) -> &Frame {
+        current_frame(&self.stack)
+    }
+
+    /// Looks up a global variable or builtin.
+    pub fn get_global(&self, name: &str) -> Option<LoxType> {
+        stack::lookup(&self.stack, name)
+    }
+
+    /// All global variables, sorted by name.
+    pub fn global_bindings(&self) -> Vec<(String, LoxType)> {
+        stack::bindings(&self.stack)
+    }
+
+    /// Discards all interpreter state, starting over with only the prelude.
+    pub fn reset(&mut self) {
+        self.clear_stack();
+        self.stack = global_stack();
+    }
+
+    /// A function stored in a variable of a frame it captured keeps that frame
+    /// alive, so the frames are emptied rather than left to `Rc`.
+    fn clear_stack(&self) {
+        for frame in self.stack.iter() {
+            frame.clear();
+        }
+    }
+
+    /// Binds a global function implemented in Rust.
+    pub fn define_native(
+        &self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Context, &[LoxType]) -> LoxResult<LoxType> + 'static,
+    ) {
+        let native = NativeFunction::new(name, arity, function);
+        self.globals()
+            .define(name, LoxType::NativeFunction(Rc::new(native)));
+    }
+
+    /// Parses `source` as a whole program and executes it in the global scope.
+    pub fn run(&self, source: &str) -> LoxResult<()> {
+        let program = (self.program_parser)((self.lexer)(source)?.as_ref())?;
+        self.execute_all(&program)
+    }
+
+    /// Evaluates `source` as a single expression if it is one, returning its
+    /// value, and otherwise executes it as a program.
+    pub fn eval_str(&self, source: &str) -> LoxResult<Option<LoxType>> {
+        let tokens = (self.lexer)(source)?;
+        match (self.parser)(&tokens) {
+            Ok(expr) => self.eval(&expr).map(Some),
+            Err(_) => {
+                self.execute_all(&(self.program_parser)(&tokens)?)?;
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn execute_all(&self, program: &[Stmt]) -> LoxResult<()> {
+        for stmt in program {
+            self.execute(stmt, &self.stack)?;
+        }
+        Ok(())
+    }
+
+    fn execute_block(&self, statements: &[Stmt], stack: &ConsList<Frame>) -> LoxResult<Flow> {
+        for stmt in statements {
+            match self.execute(stmt, stack)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn execute(&self, stmt: &Stmt, stack: &ConsList<Frame>) -> LoxResult<Flow> {
+        match &stmt.kind {
+            StmtKind::Expression(expr) => {
+                self.eval_rec(expr, stack)?;
+            }
+            StmtKind::Print(expr) => {
+                let value = self.eval_rec(expr, stack)?;
+                writeln!(self.output.borrow_mut(), "{}", value)?;
+            }
+            StmtKind::Var(name, initializer) => {
+                let value = match initializer {
+                    Some(expr) => self.eval_rec(expr, stack)?,
+                    None => LoxType::Nil,
+                };
+                current_frame(stack).define(name, value);
+            }
+            StmtKind::Block(statements) => {
+                return self.execute_block(statements, &stack.append(Frame::default()));
+            }
+            StmtKind::If(condition, then_branch, else_branch) => {
+                if self.eval_rec(condition, stack)?.is_truthy() {
+                    return self.execute(then_branch, stack);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute(else_branch, stack);
+                }
+            }
+            StmtKind::While(condition, body, increment) => {
+                while self.eval_rec(condition, stack)?.is_truthy() {
+                    match self.execute(body, stack)? {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => break,
+                        flow => return Ok(flow),
                     }
+                    if let Some(increment) = increment {
+                        self.eval_rec(increment, stack)?;
+                    }
+                }
+            }
+            StmtKind::Function(decl) => {
+                current_frame(stack).define_function(decl.clone());
+            }
+            StmtKind::Return(value) => {
+                let value = match value {
+                    Some(expr) => self.eval_rec(expr, stack)?,
+                    None => LoxType::Nil,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn call_value(&self, callee: &LoxType, args: &[LoxType], span: Span) -> LoxResult<LoxType> {
+        match callee {
+            LoxType::Function(function) => {
+                check_arity(function.arity(), args.len(), span)?;
+                if self.depth.get() >= self.max_call_depth {
+                    Err(RuntimeError::new("Stack overflow.", span))?
+                }
+                let frame = Frame::default();
+                for (param, arg) in function.decl.params.iter().zip(args) {
+                    frame.define(param, arg.clone());
+                }
+                let scope = function.closure.append(frame);
+                self.depth.set(self.depth.get() + 1);
+                let flow = self.execute_block(&function.decl.body, &scope);
+                self.depth.set(self.depth.get() - 1);
+                match flow? {
+                    Flow::Return(value) => Ok(value),
+                    Flow::Normal => Ok(LoxType::Nil),
+                    Flow::Break | Flow::Continue => {
+                        unreachable!("The parser rejects break and continue outside loops")
+                    }
+                }
+            }
+            LoxType::NativeFunction(native) => {
+                if let Arity::Fixed(arity) = native.arity {
+                    check_arity(arity, args.len(), span)?;
+                }
+                native.call(&mut Context::new(self, span), args)
+            }
+            _ => Err(RuntimeError::new(
+                "Can only call functions and classes.",
+                span,
+            ))?,
+        }
+    }
+
+    fn eval_rec(&self, ast: &Expr, stack: &ConsList<Frame>) -> LoxResult<LoxType> {
+        Ok(match &ast.kind {
+            ExprKind::Literal(lit) => lit.clone(),
+            ExprKind::Variable(name) => match stack::lookup(stack, name) {
+                Some(value) => value,
+                None => Err(undefined_variable(name, ast.span))?,
+            },
+            ExprKind::Assign(name, value) => {
+                let value = self.eval_rec(value, stack)?;
+                if !stack.iter().any(|frame| frame.assign(name, value.clone())) {
+                    Err(undefined_variable(name, ast.span))?
+                }
+                value
+            }
+            ExprKind::Logical(left, op, right) => {
+                let left = self.eval_rec(left, stack)?;
+                match (op, left.is_truthy()) {
+                    (LogicalOp::Or, true) | (LogicalOp::And, false) => left,
+                    _ => self.eval_rec(right, stack)?,
+                }
+            }
+            ExprKind::Conditional(condition, then, otherwise) => {
+                if self.eval_rec(condition, stack)?.is_truthy() {
+                    self.eval_rec(then, stack)?
+                } else {
+                    self.eval_rec(otherwise, stack)?
                 }
-                Expr::Binary(first, op, second) => {
-                    let first_val = &self.eval_rec(first.as_ref(), stack)?;
-                    let second_val = &self.eval_rec(second.as_ref(), stack)?;
-                    match op {
-                        BinaryOp::BangEqual => self.eval_rec(&Expr::Unary(
-                            UnaryOp::Bang,
-                            Box::new(Expr::Binary(
-                                Box::new(*first.clone()),
-                                BinaryOp::EqualEqual,
-                                Box::new(*second.clone()),
-                            )),
-                        ), stack)?,
-                        BinaryOp::EqualEqual => match (first_val, second_val) {
-                            (Expr::Literal(first_lit), Expr::Literal(second_lit)) => {
-                                match (first_lit, second_lit) {
-                                    (LoxType::Number(first), LoxType::Number(second)) => {
-                                        unimplemented!("Comparing floats")
-                                    }
-                                    (LoxType::Boolean(first), LoxType::Boolean(second)) => {
-                                        Expr::Literal(LoxType::Boolean(first == second))
-                                    }
-                                    (LoxType::String_(first), LoxType::String_(second)) => {
-                                        Expr::Literal(LoxType::Boolean(first == second))
-                                    }
-                                    (LoxType::Identifier(first), LoxType::Identifier(second)) => {
-                                        unimplemented!("Comparing identifiers")
-                                    }
-                                    (LoxType::Identifier(first), second) => {
-                                        unimplemented!("Comparing identifiers")
-                                    }
-                                    (first, LoxType::Identifier(second)) => {
-                                        unimplemented!("Comparing identifiers")
-                                    }
-                                    (LoxType::Nil, LoxType::Nil) => {
-                                        Expr::Literal(LoxType::Boolean(true))
-                                    }
-                                    (_, _) => Expr::Literal(LoxType::Boolean(false)),
-                                }
+            }
+            ExprKind::Call(callee, args) => {
+                let callee = self.eval_rec(callee, stack)?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval_rec(arg, stack))
+                    .collect::<LoxResult<Vec<_>>>()?;
+                self.call_value(&callee, &args, ast.span)?
+            }
+            ExprKind::Get(object, name) => match self.eval_rec(object, stack)? {
+                LoxType::NativeObject(object) => match object.get_property(name) {
+                    Some(value) => value,
+                    None if object.has_method(name) => bind_method(object, name),
+                    None => Err(RuntimeError::new(
+                        format!("Undefined property '{}'.", name),
+                        ast.span,
+                    ))?,
+                },
+                LoxType::String_(s) => match strings::method(&s, name) {
+                    Some(method) => method,
+                    None => Err(RuntimeError::new(
+                        format!("Undefined property '{}'.", name),
+                        ast.span,
+                    ))?,
+                },
+                LoxType::List(list) => match lists::method(&list, name) {
+                    Some(method) => method,
+                    None => Err(RuntimeError::new(
+                        format!("Undefined property '{}'.", name),
+                        ast.span,
+                    ))?,
+                },
+                LoxType::Map(map) => match maps::method(&map, name) {
+                    Some(method) => method,
+                    None => Err(RuntimeError::new(
+                        format!("Undefined property '{}'.", name),
+                        ast.span,
+                    ))?,
+                },
+                _ => Err(RuntimeError::new(
+                    "Only instances have properties.",
+                    ast.span,
+                ))?,
+            },
+            ExprKind::Set(object, name, value) => {
+                let object = match self.eval_rec(object, stack)? {
+                    LoxType::NativeObject(object) => object,
+                    _ => Err(RuntimeError::new("Only instances have fields.", ast.span))?,
+                };
+                let value = self.eval_rec(value, stack)?;
+                object.set_property(&mut Context::new(self, ast.span), name, value.clone())?;
+                value
+            }
+            ExprKind::List(elements) => LoxType::list(
+                elements
+                    .iter()
+                    .map(|element| self.eval_rec(element, stack))
+                    .collect::<LoxResult<_>>()?,
+            ),
+            ExprKind::Map(entries) => {
+                let mut map = LoxMap::default();
+                for (key, value) in entries {
+                    let key_span = key.span;
+                    let key = map_key(&self.eval_rec(key, stack)?, key_span)?;
+                    map.insert(key, self.eval_rec(value, stack)?);
+                }
+                LoxType::map(map)
+            }
+            ExprKind::Index(object, index) => {
+                let object_span = object.span;
+                let object = self.eval_rec(object, stack)?;
+                let index = self.eval_rec(index, stack)?;
+                match object {
+                    LoxType::List(list) => {
+                        let elements = list.borrow();
+                        elements[list_index(&index, elements.len(), ast.span)?].clone()
+                    }
+                    LoxType::Map(map) => {
+                        let key = map_key(&index, ast.span)?;
+                        match map.borrow().get(&key) {
+                            Some(value) => value.clone(),
+                            None => Err(RuntimeError::new(
+                                format!("Key {} isn't in the map.", key),
+                                ast.span,
+                            ))?,
+                        }
+                    }
+                    _ => Err(not_indexable(object_span))?,
+                }
+            }
+            ExprKind::SetIndex(object, index, value) => {
+                let object_span = object.span;
+                let object = self.eval_rec(object, stack)?;
+                let index = self.eval_rec(index, stack)?;
+                let value = self.eval_rec(value, stack)?;
+                match object {
+                    LoxType::List(list) => {
+                        let mut elements = list.borrow_mut();
+                        let index = list_index(&index, elements.len(), ast.span)?;
+                        elements[index] = value.clone();
+                    }
+                    LoxType::Map(map) => {
+                        let key = map_key(&index, ast.span)?;
+                        map.borrow_mut().insert(key, value.clone());
+                    }
+                    _ => Err(not_indexable(object_span))?,
+                }
+                value
+            }
+            ExprKind::Unary(op, expr) => {
+                let val = self.eval_rec(expr, stack)?;
+                match (op, val) {
+                    (UnaryOp::Bang, val) => LoxType::Boolean(!val.is_truthy()),
+                    (UnaryOp::Minus, LoxType::Number(num)) => LoxType::Number(-num),
+                    (UnaryOp::Tilde, LoxType::Number(num)) => {
+                        let result = !safe_integer(num, ast.span)?;
+                        LoxType::Number(safe_result(Some(result), ast.span, || {
+                            format!("~{}", num)
+                        })?)
+                    }
+                    (UnaryOp::Minus, _) | (UnaryOp::Tilde, _) => {
+                        Err(RuntimeError::new("Operand must be a number.", ast.span))?
+                    }
+                }
+            }
+            ExprKind::Binary(first, op, second) => {
+                let first_val = self.eval_rec(first, stack)?;
+                let second_val = self.eval_rec(second, stack)?;
+                match op {
+                    BinaryOp::Comma => second_val,
+                    BinaryOp::EqualEqual => LoxType::Boolean(first_val == second_val),
+                    BinaryOp::BangEqual => LoxType::Boolean(first_val != second_val),
+                    BinaryOp::Plus => match (first_val, second_val) {
+                        (LoxType::Number(first), LoxType::Number(second)) => {
+                            LoxType::Number(first + second)
+                        }
+                        (LoxType::String_(first), LoxType::String_(second)) => {
+                            LoxType::String_(first + &second)
+                        }
+                        _ => Err(RuntimeError::new(
+                            "Operands must be two numbers or two strings.",
+                            ast.span,
+                        ))?,
+                    },
+                    _ => {
+                        let (first, second) = match (first_val, second_val) {
+                            (LoxType::Number(first), LoxType::Number(second)) => (first, second),
+                            _ => Err(RuntimeError::new("Operands must be numbers.", ast.span))?,
+                        };
+                        match op {
+                            BinaryOp::Greater => LoxType::Boolean(first > second),
+                            BinaryOp::GreaterEqual => LoxType::Boolean(first >= second),
+                            BinaryOp::Less => LoxType::Boolean(first < second),
+                            BinaryOp::LessEqual => LoxType::Boolean(first <= second),
+                            BinaryOp::Minus => LoxType::Number(first - second),
+                            BinaryOp::Star => LoxType::Number(first * second),
+                            BinaryOp::Slash => LoxType::Number(first / second),
+                            BinaryOp::Percent | BinaryOp::TildeSlash if second == 0.0 => {
+                                Err(RuntimeError::new("Division by zero.", ast.span))?
                             }
-                            _ => panic!(
-                                "{:?} or {:?} did not evaluate to literals",
-                                first_val, second_val
-                            ),
-                        },
-                        BinaryOp::Greater => match (first_val, second_val) {
-                            (
-                                Expr::Literal(LoxType::Number(first)),
-                                Expr::Literal(LoxType::Number(second)),
-                            ) => Expr::Literal(LoxType::Boolean(first > second)),
-                            _ => panic!("Can't apply > on {:?} and {:?}", first_val, second_val),
-                        },
-                        BinaryOp::GreaterEqual => match (first_val, second_val) {
-                            (
-                                Expr::Literal(LoxType::Number(first)),
-                                Expr::Literal(LoxType::Number(second)),
-                            ) => Expr::Literal(LoxType::Boolean(first >= second)),
-                            _ => panic!("Can't apply >= on {:?} and {:?}", first_val, second_val),
-                        },
-                        BinaryOp::Less => match (first_val, second_val) {
-                            (
-                                Expr::Literal(LoxType::Number(first)),
-                                Expr::Literal(LoxType::Number(second)),
-                            ) => Expr::Literal(LoxType::Boolean(first < second)),
-                            _ => panic!("Can't apply < on {:?} and {:?}", first_val, second_val),
-                        },
-                        BinaryOp::LessEqual => match (first_val, second_val) {
-                            (
-                                Expr::Literal(LoxType::Number(first)),
-                                Expr::Literal(LoxType::Number(second)),
-                            ) => Expr::Literal(LoxType::Boolean(first <= second)),
-                            _ => panic!("Can't apply <= on {:?} and {:?}", first_val, second_val),
-                        },
-                        BinaryOp::Plus => match (first_val, second_val) {
-                            (
-                                Expr::Literal(LoxType::Number(first)),
-                                Expr::Literal(LoxType::Number(second)),
-                            ) => Expr::Literal(LoxType::Number(first + second)),
-                            _ => panic!("Can't add {:?} and {:?}", first_val, second_val),
-                        },
-                        BinaryOp::Minus => match (first_val, second_val) {
-                            (
-                                Expr::Literal(LoxType::Number(first)),
-                                Expr::Literal(LoxType::Number(second)),
-                            ) => Expr::Literal(LoxType::Number(first - second)),
-                            _ => panic!("Can't subtract {:?} and {:?}", first_val, second_val),
-                        },
-                        BinaryOp::Star => match (first_val, second_val) {
-                            (
-                                Expr::Literal(LoxType::Number(first)),
-                                Expr::Literal(LoxType::Number(second)),
-                            ) => Expr::Literal(LoxType::Number(first * second)),
-                            _ => panic!("Can't multiply {:?} and {:?}", first_val, second_val),
-                        },
-                        BinaryOp::Slash => match (first_val, second_val) {
-                            (
-                                Expr::Literal(LoxType::Number(first)),
-                                Expr::Literal(LoxType::Number(second)),
-                            ) => Expr::Literal(LoxType::Number(first / second)),
-                            _ => panic!("Can't divide {:?} and {:?}", first_val, second_val),
-                        },
+                            BinaryOp::Percent => LoxType::Number(floored_remainder(first, second)),
+                            BinaryOp::TildeSlash => LoxType::Number((first / second).floor()),
+                            BinaryOp::StarStar => LoxType::Number(first.powf(second)),
+                            BinaryOp::Ampersand
+                            | BinaryOp::Pipe
+                            | BinaryOp::Caret
+                            | BinaryOp::LessLess
+                            | BinaryOp::GreaterGreater => {
+                                LoxType::Number(bitwise(op, first, second, ast.span)?)
+                            }
+                            BinaryOp::Comma
+                            | BinaryOp::EqualEqual
+                            | BinaryOp::BangEqual
+                            | BinaryOp::Plus => {
+                                unreachable!()
+                            }
+                        }
                     }
                 }
             }
@@ -150,20 +487,463 @@ impl<'a> AstIntepreter<'a> {
     }
 }
 
+/// The remainder of `first ~/ second`, which takes the sign of `second` as in
+/// Python, so that `(a ~/ b) * b + a % b == a`.
+fn floored_remainder(first: f64, second: f64) -> f64 {
+    let remainder = first % second;
+    if remainder != 0.0 && (remainder < 0.0) != (second < 0.0) {
+        remainder + second
+    } else {
+        remainder
+    }
+}
+
+/// Largest integer that every smaller integer can also be stored exactly as
+/// an `f64`, 2^53 - 1.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// `num` as an `i64`, failing unless it's an integer in the safe range.
+fn safe_integer(num: f64, span: Span) -> LoxResult<i64> {
+    if num.fract() != 0.0 || !num.is_finite() {
+        Err(RuntimeError::new(
+            format!("Bitwise operands must be integers but got {}.", num),
+            span,
+        ))?
+    }
+    if num.abs() > MAX_SAFE_INTEGER {
+        Err(RuntimeError::new(
+            format!("Bitwise operand {} is outside the 53-bit safe range.", num),
+            span,
+        ))?
+    }
+    Ok(num as i64)
+}
+
+/// Applies a bitwise or shift operator to the operands as two's complement
+/// integers. `>>` keeps the sign.
+fn bitwise(op: &BinaryOp, first: f64, second: f64, span: Span) -> LoxResult<f64> {
+    let first = safe_integer(first, span)?;
+    let second = safe_integer(second, span)?;
+    let result = match op {
+        BinaryOp::Ampersand => Some(first & second),
+        BinaryOp::Pipe => Some(first | second),
+        BinaryOp::Caret => Some(first ^ second),
+        BinaryOp::LessLess | BinaryOp::GreaterGreater if !(0..64).contains(&second) => {
+            Err(RuntimeError::new(
+                format!("Shift count must be from 0 to 63 but got {}.", second),
+                span,
+            ))?
+        }
+        BinaryOp::LessLess => first.checked_mul(1 << second),
+        BinaryOp::GreaterGreater => Some(first >> second),
+        _ => unreachable!("{} isn't a bitwise operator", op),
+    };
+    safe_result(result, span, || format!("{} {} {}", first, op, second))
+}
+
+/// The result of a bitwise operator as a number, failing if it overflowed or
+/// left the safe range, as `~(2^53 - 1)` does. `describe` gives the operation.
+fn safe_result(
+    result: Option<i64>,
+    span: Span,
+    describe: impl FnOnce() -> String,
+) -> LoxResult<f64> {
+    match result {
+        Some(result) if result.unsigned_abs() as f64 <= MAX_SAFE_INTEGER => Ok(result as f64),
+        _ => Err(RuntimeError::new(
+            format!("{} is outside the 53-bit safe range.", describe()),
+            span,
+        ))?,
+    }
+}
+
+fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_nanos() as u64)
+}
+
+/// A stack holding just an empty global frame on top of the builtins.
+fn global_stack() -> ConsList<Frame> {
+    let builtins = Frame::default();
+    prelude::install(&builtins);
+    ConsList::new().append(builtins).append(Frame::default())
+}
+
+fn current_frame(stack: &ConsList<Frame>) -> &Frame {
+    stack.head().expect("Global frame is always present")
+}
+
+/// `object.name` as a function value, calling back into the object's method.
+fn bind_method(object: Rc<dyn NativeObject>, name: &str) -> LoxType {
+    let method = name.to_string();
+    let bound = NativeFunction::new(name, Arity::Variadic, move |context, args| {
+        object.call_method(context, &method, args)
+    });
+    LoxType::NativeFunction(Rc::new(bound))
+}
+
+fn check_arity(expected: usize, got: usize, span: Span) -> LoxResult<()> {
+    if expected != got {
+        Err(RuntimeError::new(
+            format!("Expected {} arguments but got {}.", expected, got),
+            span,
+        ))?
+    }
+    Ok(())
+}
+
+fn list_index(index: &LoxType, len: usize, span: Span) -> LoxResult<usize> {
+    native::check_index(index, len, false)
+        .map_err(|message| RuntimeError::new(format!("List {}", message), span).into())
+}
+
+fn map_key(key: &LoxType, span: Span) -> LoxResult<MapKey> {
+    MapKey::new(key).map_err(|message| RuntimeError::new(message, span).into())
+}
+
+fn not_indexable(span: Span) -> RuntimeError {
+    RuntimeError::new("Only lists and maps can be indexed.", span)
+}
+
+fn undefined_variable(name: &str, span: Span) -> RuntimeError {
+    RuntimeError::new(format!("Undefined variable '{}'.", name), span)
+}
+
+impl Host for AstIntepreter<'_> {
+    fn call(&self, callee: &LoxType, args: &[LoxType], span: Span) -> LoxResult<LoxType> {
+        self.call_value(callee, args, span)
+    }
+
+    fn get_global(&self, name: &str) -> Option<LoxType> {
+        AstIntepreter::get_global(self, name)
+    }
+
+    fn set_global(&self, name: &str, value: LoxType) {
+        self.globals().define(name, value);
+    }
+
+    fn read_line(&self) -> LoxResult<Option<String>> {
+        let mut line = String::new();
+        if self.input.borrow_mut().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    fn random(&self) -> f64 {
+        self.rng.borrow_mut().next_f64()
+    }
+
+    fn seed_random(&self, seed: u64) {
+        AstIntepreter::seed_random(self, seed);
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn args(&self) -> Vec<String> {
+        self.args.clone()
+    }
+}
+
+impl Drop for AstIntepreter<'_> {
+    fn drop(&mut self) {
+        self.clear_stack();
+    }
+}
+
 impl Default for AstIntepreter<'_> {
     fn default() -> Self {
-        Self::new(&scan_tokens, &parse_tokens)
+        Self::new(&scan_tokens, &parse_tokens, &parse_program)
     }
 }
 
 impl LoxInterpreter<&str> for AstIntepreter<'_> {
-    fn eval(&self, input: &str) -> LoxResult<Expr> {
+    fn eval(&self, input: &str) -> LoxResult<LoxType> {
         self.eval(&(self.parser)((self.lexer)(input)?.as_ref())?)
     }
 }
 
 impl LoxInterpreter<&Expr> for AstIntepreter<'_> {
-    fn eval(&self, ast: &Expr) -> LoxResult<Expr> {
-        self.eval_rec(ast, &ConsList::new().append(Frame::default()))
+    fn eval(&self, ast: &Expr) -> LoxResult<LoxType> {
+        self.eval_rec(ast, &self.stack)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::LoxError;
+    use crate::test_util::{assert_runtime_errors, with_big_stack};
+    use crate::Lox;
+
+    use super::*;
+
+    fn run(source: &str) -> LoxResult<String> {
+        let mut out = Vec::new();
+        {
+            let mut vm = AstIntepreter::default();
+            vm.set_output(&mut out);
+            vm.run(source)?;
+        }
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_print_and_variables() {
+        assert_eq!(
+            run("var a = 1; var b; print a + 2; print b; a = \"x\"; print a;").unwrap(),
+            "3\nnil\nx\n"
+        );
+    }
+
+    #[test]
+    fn test_block_scoping() {
+        let source = "var a = 1; { var a = 2; print a; a = 3; } print a; { a = 4; } print a;";
+        assert_eq!(run(source).unwrap(), "2\n1\n4\n");
+    }
+
+    #[test]
+    fn test_control_flow() {
+        assert_eq!(
+            run("for (var i = 0; i < 3; i = i + 1) { if (i == 1) print \"one\"; else print i; }")
+                .unwrap(),
+            "0\none\n2\n"
+        );
+        assert_eq!(
+            run("var n = 3; while (n > 0) n = n - 1; print n;").unwrap(),
+            "0\n"
+        );
+        assert_eq!(
+            run("print nil or \"b\"; print false and 1; print 0 and 2;").unwrap(),
+            "b\nfalse\n2\n"
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_operators() {
+        assert_eq!(
+            run("print 7 % 3; print -7 % 3; print 7 % -3; print 7.5 % 2;").unwrap(),
+            "1\n2\n-2\n1.5\n"
+        );
+        assert_eq!(
+            run("print 7 ~/ 2; print -7 ~/ 2; print 7 ~/ -2; print 7.5 ~/ 2;").unwrap(),
+            "3\n-4\n-4\n3\n"
+        );
+        assert_eq!(
+            run("print 2 ** 10; print 2 ** 3 ** 2; print -2 ** 2; print 2 ** -1;").unwrap(),
+            "1024\n512\n-4\n0.5\n"
+        );
+        assert_eq!(
+            run("print 1 + 2 * 3 % 4; print 2 * 3 ** 2; print (-8) ** 0.5; print 1 / 0;").unwrap(),
+            "3\n18\nNaN\ninf\n"
+        );
+        assert_runtime_errors(
+            &mut Lox::new(),
+            &[
+                "print 1 % 0;",
+                "print 1 ~/ 0;",
+                "print -1 % -0;",
+                "print 2 ** \"x\";",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        assert_eq!(
+            run("print 12 & 10; print 12 | 10; print 12 ^ 10; print ~5; print ~-1;").unwrap(),
+            "8\n14\n6\n-6\n0\n"
+        );
+        assert_eq!(
+            run("print 1 << 52; print -1 << 3; print -16 >> 2; print 5 >> 63;").unwrap(),
+            "4503599627370496\n-8\n-4\n0\n"
+        );
+        assert_eq!(
+            run("print 1 | 2 ^ 3 & 4 << 1; print 6 & 3 == 2; print 1 + 1 << 2;").unwrap(),
+            "3\ntrue\n8\n"
+        );
+        assert_eq!(
+            run("print 9007199254740991 & -1; print ~9007199254740990;").unwrap(),
+            "9007199254740991\n-9007199254740991\n"
+        );
+        assert_runtime_errors(
+            &mut Lox::new(),
+            &[
+                "print 1.5 & 1;",
+                "print ~0.5;",
+                "print 9007199254740992 | 0;",
+                "print ~9007199254740991;",
+                "print 9007199254740991 ^ -1;",
+                "print 1 << 53;",
+                "print 1 << 63;",
+                "print 1 << -1;",
+                "print 1 >> 64;",
+                "print ~\"1\";",
+                "print 1 & nil;",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_conditional_and_comma() {
+        assert_eq!(
+            run("print 1 < 2 ? \"yes\" : \"no\"; print nil ? 1 : false ? 2 : 3;").unwrap(),
+            "yes\n3\n"
+        );
+        assert_eq!(
+            run("var a = 0; var b = true ? a = 1 : (a = 2); print a; print b;").unwrap(),
+            "1\n1\n"
+        );
+        assert_eq!(
+            run("fun f() { print \"x\"; } var a = (f(), 2); print a; print [1, (2, 3)];").unwrap(),
+            "x\n2\n[1, 3]\n"
+        );
+        let source = "var s = 0; var j;
+            for (var i = (j = 3, 0); i < j; i = i + 1, j = j - 1) s = s + i * j;
+            print s;";
+        assert_eq!(run(source).unwrap(), "2\n");
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        let source = "
+            for (var i = 0; i < 10; i = i + 1) {
+                var half = i / 2;
+                if (i % 2 == 1) continue;
+                if (i > 6) break;
+                print half;
+            }";
+        assert_eq!(run(source).unwrap(), "0\n1\n2\n3\n");
+        let source = "
+            var i = 0;
+            while (true) {
+                i = i + 1;
+                { var j = 0; while (j < i) { j = j + 1; if (j == 2) break; } print i * 10 + j; }
+                if (i < 3) continue;
+                break;
+            }
+            print i;";
+        assert_eq!(run(source).unwrap(), "11\n22\n32\n3\n");
+        let source = "
+            fun find(n) { for (var i = 0;; i = i + 1) if (i * i >= n) return i; }
+            var n = 0;
+            for (;;) { n = n + 1; if (n < 3) continue; break; }
+            print find(10) + n;";
+        assert_eq!(run(source).unwrap(), "7\n");
+    }
+
+    #[test]
+    fn test_equality_and_truthiness() {
+        assert_eq!(
+            run("print 0.5 == 0.5; print 1 == \"1\"; print nil != false; print !nil;").unwrap(),
+            "true\nfalse\ntrue\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let source = "var a = 1;\nprint -\"x\";";
+        match run(source) {
+            Err(LoxError::InnerRuntimeError(err)) => {
+                assert_eq!(err.message, "Operand must be a number.");
+                assert_eq!(err.span.line_in(source), 2);
+            }
+            res => panic!("Expected a runtime error, got {:?}", res),
+        }
+        assert_runtime_errors(
+            &mut Lox::new(),
+            &[
+                "print 1 < \"2\";",
+                "print x;",
+                "x = 1;",
+                "{ var y; } y = 1;",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let source = "
+            fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+            fun noop() {}
+            print fib(10);
+            print noop();
+            print fib;
+        ";
+        assert_eq!(run(source).unwrap(), "55\nnil\n<fn fib>\n");
+    }
+
+    #[test]
+    fn test_closures() {
+        let source = "
+            fun counter() { var n = 0; fun next() { n = n + 1; return n; } return next; }
+            var a = counter();
+            var b = counter();
+            a(); a(); b();
+            print a();
+            print b();
+            print a == b;
+            print counter == counter;
+            var c = a;
+            print a == c;
+        ";
+        assert_eq!(run(source).unwrap(), "3\n2\nfalse\ntrue\ntrue\n");
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        with_big_stack(|| {
+            let source = "
+                fun depth(n) {
+                    if (n > 0) { return 1 + depth(n - 1); }
+                    return 0;
+                }
+                print depth(4999);
+                fun sum(list, i) {
+                    if (i == list.len()) { return 0; }
+                    return list[i] + sum(list, i + 1);
+                }
+                var list = [];
+                for (var i = 0; i < 150; i = i + 1) { list.push(i); }
+                print sum(list, 0);";
+            assert_eq!(run(source).unwrap(), "4999\n11175\n");
+
+            let mut vm = AstIntepreter::default();
+            vm.set_output(Vec::new());
+            match vm.run("fun forever(n) { return forever(n + 1); } forever(0);") {
+                Err(LoxError::InnerRuntimeError(err)) => {
+                    assert_eq!(err.message, "Stack overflow.")
+                }
+                res => panic!("Expected a stack overflow, got {:?}", res),
+            }
+            vm.run("fun once() { return 1; } once();").unwrap();
+        });
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        let mut vm = AstIntepreter::default();
+        vm.set_output(Vec::new());
+        vm.set_max_call_depth(10);
+        vm.run("fun depth(n) { if (n > 0) { return 1 + depth(n - 1); } return 0; }")
+            .unwrap();
+        assert_eq!(vm.eval_str("depth(9)").unwrap(), Some(LoxType::Number(9.0)));
+        match vm.eval_str("depth(10)") {
+            Err(LoxError::InnerRuntimeError(err)) => assert_eq!(err.message, "Stack overflow."),
+            res => panic!("Expected a stack overflow, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_call_errors() {
+        assert_runtime_errors(&mut Lox::new(), &["fun f(a) {} f();", "\"f\"();", "nil();"]);
     }
 }
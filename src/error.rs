@@ -1,11 +1,13 @@
+#![allow(non_local_definitions)]
+
 use std::io;
 
-use crate::token::{Token, TokenType};
+use crate::token::{Span, Token, TokenType};
 use failure::Fail;
-use std::num::ParseFloatError;
 
 pub type LoxResult<T> = Result<T, LoxError>;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Fail)]
 pub enum LoxError {
     #[fail(display = "IO Error: {}", _0)]
@@ -14,34 +16,100 @@ pub enum LoxError {
     InnerLexingError(#[cause] LexingError),
     #[fail(display = "Parsing Error: {}", _0)]
     InnerParsingError(#[cause] ParsingError),
+    #[fail(display = "Runtime Error: {}", _0)]
+    InnerRuntimeError(#[cause] RuntimeError),
+    /// A script called `exit` with this status.
+    #[fail(display = "Exited with status {}", _0)]
+    Exit(i32),
 }
 
 #[derive(Debug, Fail)]
 pub enum LexingError {
-    #[fail(display = "Invalid Token '{}'", _0)]
-    InvalidToken(char),
+    #[fail(display = "Invalid Token '{}' on line number {}", token, line_num)]
+    InvalidToken {
+        line_num: usize,
+        span: Span,
+        token: char,
+    },
     #[fail(
         display = "String literal unexpected ended on line number {}",
         line_num
     )]
     UnexpectedEndStringLiteral { line_num: usize },
     #[fail(
-        display = "Could not parse digit on line number {}. Failed with error: {}",
-        line_num, err
+        display = "Block comment starting on line number {} was never closed",
+        line_num
+    )]
+    UnterminatedComment { line_num: usize },
+    #[fail(
+        display = "Invalid escape sequence '\\{}' on line number {}",
+        escape, line_num
+    )]
+    InvalidEscape { line_num: usize, escape: String },
+    #[fail(
+        display = "Invalid number literal on line number {}: {}",
+        line_num, reason
     )]
     InvalidDigit {
         line_num: usize,
-        #[cause]
-        err: ParseFloatError,
+        span: Span,
+        reason: String,
     },
 }
 
 #[derive(Debug, Fail)]
 pub enum ParsingError {
-    #[fail(display = "Unexpected Token '{:?}'", _0)]
+    #[fail(display = "Unexpected {}", _0)]
     UnexpectedToken(Token),
-    #[fail(display = "Expected Token '{:?}'", _0)]
-    ExpectedToken(TokenType),
+    #[fail(display = "Expected {} but found {}", _0, _1)]
+    ExpectedToken(TokenType, Token),
+    #[fail(
+        display = "Expected ':' after the branch of '?' on line {} but found {}",
+        question_line, found
+    )]
+    MissingColon { question_line: u32, found: Token },
+    #[fail(display = "Invalid assignment target {}", _0)]
+    InvalidAssignmentTarget(Token),
+    #[fail(display = "Can't return from top-level code at {}", _0)]
+    ReturnOutsideFunction(Token),
+    #[fail(display = "Can't use {} outside a loop", _0)]
+    OutsideLoop(Token),
+    #[fail(display = "Too deeply nested at {}", _0)]
+    TooDeep(Token),
+    #[fail(display = "Unexpected end of input")]
+    UnexpectedEof,
+}
+
+impl ParsingError {
+    /// Whether parsing failed only because the input ended too early.
+    pub fn at_eof(&self) -> bool {
+        match self {
+            ParsingError::UnexpectedToken(token)
+            | ParsingError::ExpectedToken(_, token)
+            | ParsingError::MissingColon { found: token, .. } => token.token_type == TokenType::Eof,
+            ParsingError::InvalidAssignmentTarget(_)
+            | ParsingError::ReturnOutsideFunction(_)
+            | ParsingError::OutsideLoop(_)
+            | ParsingError::TooDeep(_) => false,
+            ParsingError::UnexpectedEof => true,
+        }
+    }
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "{}", message)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>, span: Span) -> RuntimeError {
+        RuntimeError {
+            message: message.into(),
+            span,
+        }
+    }
 }
 
 impl From<io::Error> for LoxError {
@@ -61,3 +129,9 @@ impl From<ParsingError> for LoxError {
         LoxError::InnerParsingError(err)
     }
 }
+
+impl From<RuntimeError> for LoxError {
+    fn from(err: RuntimeError) -> Self {
+        LoxError::InnerRuntimeError(err)
+    }
+}
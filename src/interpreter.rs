@@ -1,6 +1,6 @@
-use crate::ast::Expr;
 use crate::error::LoxResult;
+use crate::types::LoxType;
 
 pub trait LoxInterpreter<T> {
-    fn eval(&self, input: T) -> LoxResult<Expr>;
+    fn eval(&self, input: T) -> LoxResult<LoxType>;
 }
@@ -1,203 +1,452 @@
-use itertools::Itertools;
+use std::collections::VecDeque;
+use std::mem;
+
+#[cfg(feature = "unicode-idents")]
+use unicode_xid::UnicodeXID;
 
 use crate::error::LexingError;
+use crate::error::LoxError;
 use crate::error::LoxResult;
+use crate::token::Span;
 use crate::token::Token;
 use crate::token::TokenType;
 use crate::token::TokenType::*;
 
 pub fn scan_tokens(text: &str) -> LoxResult<Vec<Token>> {
-    let mut tokens: Vec<Token> = Vec::new();
+    Lexer::new(text.chars()).collect()
+}
 
-    {
-        let mut line_num: usize = 0;
+/// Streaming lexer over any source of `char`s.
+///
+/// Tokens are scanned lazily as the lexer is iterated, so the input never has
+/// to be buffered as a whole. The final item is always an `Eof` token.
+pub struct Lexer<I: Iterator<Item = char>> {
+    chars: I,
+    /// Characters read from `chars` but not yet consumed.
+    pending: VecDeque<char>,
+    /// Tokens scanned ahead of time by `peek`/`peek_nth`.
+    lookahead: VecDeque<LoxResult<Token>>,
+    /// Text of the token currently being scanned.
+    current: String,
+    /// Byte offset of the next character.
+    offset: usize,
+    line_num: usize,
+    /// Column of the next character, counted in `char`s.
+    col: usize,
+    finished: bool,
+}
 
-        let mut letters = text
-            .chars()
-            .chain(vec!['\0'].into_iter())
-            .tuple_windows()
-            .enumerate();
-        while let Some((idx, letter)) = letters.next() {
-            let make_token = |tt: TokenType, slice_size: usize| {
-                Token::new(tt, text[idx..idx + slice_size].to_string(), line_num as u32)
-            };
-            match letter {
-                ('(', _) => tokens.push(make_token(LeftParen, 1)),
-                (')', _) => tokens.push(make_token(RightParen, 1)),
-                ('{', _) => tokens.push(make_token(LeftBrace, 1)),
-                ('}', _) => tokens.push(make_token(RightBrace, 1)),
-                (',', _) => tokens.push(make_token(Comma, 1)),
-                ('.', _) => tokens.push(make_token(Dot, 1)),
-                ('-', _) => tokens.push(make_token(Minus, 1)),
-                ('+', _) => tokens.push(make_token(Plus, 1)),
-                (';', _) => tokens.push(make_token(Semicolon, 1)),
-                ('*', _) => tokens.push(make_token(Star, 1)),
-                ('!', '=') => {
-                    tokens.push(make_token(BangEqual, 2));
-                    letters.next();
-                }
-                ('!', _) => tokens.push(make_token(Bang, 1)),
-                ('=', '=') => {
-                    tokens.push(make_token(EqualEqual, 2));
-                    letters.next();
-                }
-                ('=', _) => tokens.push(make_token(Equal, 1)),
-                ('<', '=') => {
-                    tokens.push(make_token(LessEqual, 2));
-                    letters.next();
-                }
-                ('<', _) => tokens.push(make_token(Less, 1)),
-                ('>', '=') => {
-                    tokens.push(make_token(GreaterEqual, 2));
-                    letters.next();
+impl<I: Iterator<Item = char>> Lexer<I> {
+    pub fn new(chars: I) -> Self {
+        Lexer {
+            chars,
+            pending: VecDeque::new(),
+            lookahead: VecDeque::new(),
+            current: String::new(),
+            offset: 0,
+            line_num: 1,
+            col: 1,
+            finished: false,
+        }
+    }
+
+    /// Returns the next token without consuming it.
+    pub fn peek(&mut self) -> Option<&LoxResult<Token>> {
+        self.peek_nth(0)
+    }
+
+    /// Returns the token `n` positions ahead without consuming anything.
+    pub fn peek_nth(&mut self, n: usize) -> Option<&LoxResult<Token>> {
+        while self.lookahead.len() <= n {
+            match self.scan_token() {
+                Some(token) => self.lookahead.push_back(token),
+                None => break,
+            }
+        }
+        self.lookahead.get(n)
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.peek_char_nth(0)
+    }
+
+    fn peek_char_nth(&mut self, n: usize) -> Option<char> {
+        while self.pending.len() <= n {
+            match self.chars.next() {
+                Some(chr) => self.pending.push_back(chr),
+                None => break,
+            }
+        }
+        self.pending.get(n).cloned()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let chr = self.pending.pop_front().or_else(|| self.chars.next())?;
+        self.offset += chr.len_utf8();
+        if chr == '\n' {
+            self.line_num += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        self.current.push(chr);
+        Some(chr)
+    }
+
+    fn advance_if(&mut self, expected: char) -> bool {
+        if self.peek_char() == Some(expected) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn scan_token(&mut self) -> Option<LoxResult<Token>> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            self.current.clear();
+            let start = self.offset;
+            let line_num = self.line_num;
+            let col = self.col;
+            let chr = match self.advance() {
+                Some(chr) => chr,
+                None => {
+                    self.finished = true;
+                    return Some(Ok(self.make_token(Eof, start, line_num, col)));
                 }
-                ('>', _) => tokens.push(make_token(Greater, 1)),
-                ('/', '/') => {
+            };
+            let token_type = match chr {
+                '(' => LeftParen,
+                ')' => RightParen,
+                '{' => LeftBrace,
+                '}' => RightBrace,
+                '[' => LeftBracket,
+                ']' => RightBracket,
+                ',' => Comma,
+                '.' => Dot,
+                '-' => Minus,
+                '+' => Plus,
+                ';' => Semicolon,
+                ':' => Colon,
+                '?' => Question,
+                '*' if self.advance_if('*') => StarStar,
+                '*' => Star,
+                '%' => Percent,
+                // Not `//`, which starts a comment
+                '~' if self.advance_if('/') => TildeSlash,
+                '~' => Tilde,
+                '&' => Ampersand,
+                '|' => Pipe,
+                '^' => Caret,
+                '!' if self.advance_if('=') => BangEqual,
+                '!' => Bang,
+                '=' if self.advance_if('=') => EqualEqual,
+                '=' => Equal,
+                '<' if self.advance_if('=') => LessEqual,
+                '<' if self.advance_if('<') => LessLess,
+                '<' => Less,
+                '>' if self.advance_if('=') => GreaterEqual,
+                '>' if self.advance_if('>') => GreaterGreater,
+                '>' => Greater,
+                '/' if self.advance_if('/') => {
                     // Ignore rest of comment
-                    loop {
-                        if let Some((_, (_, '\n'))) = letters.next() {
-                            break;
-                        }
+                    while self.peek_char().is_some_and(|chr| chr != '\n') {
+                        self.advance();
                     }
+                    continue;
                 }
-                ('/', _) => tokens.push(make_token(Slash, 1)),
-                (' ', _) => {}
-                ('\r', _) => {}
-                ('\t', _) => {}
-                ('\n', _) => {
-                    line_num += 1;
+                '/' if self.advance_if('*') => match self.handle_block_comment(line_num) {
+                    Ok(()) => continue,
+                    Err(err) => return Some(Err(err)),
+                },
+                '/' => Slash,
+                ' ' | '\r' | '\t' | '\n' => continue,
+                '"' => match self.handle_string() {
+                    Ok(token_type) => token_type,
+                    Err(err) => return Some(Err(err)),
+                },
+                '0'..='9' => match self.handle_number(chr) {
+                    Ok(token_type) => token_type,
+                    Err(err) => return Some(Err(err)),
+                },
+                chr if is_ident_start(chr) => self.handle_ident_or_keyword(),
+                token => {
+                    let span = Span::new(start, self.offset);
+                    let err = LexingError::InvalidToken {
+                        line_num,
+                        span,
+                        token,
+                    };
+                    return Some(Err(err.into()));
                 }
-                ('"', _) => {
-                    let mut string_lit: Vec<char> = Vec::new();
-                    loop {
-                        let next_letter = letters.next();
-                        match next_letter {
-                            Some((_idx, ('"', _))) => {
-                                tokens.push(make_token(
-                                    String_(string_lit.iter().collect()),
-                                    string_lit.len() + 2,
-                                ));
-                                break;
-                            }
-                            Some((_idx, (chr, _))) => {
-                                string_lit.push(chr);
-                            }
-                            None => {
-                                return Err(LexingError::UnexpectedEndStringLiteral { line_num })?
-                            }
-                        }
+            };
+            return Some(Ok(self.make_token(token_type, start, line_num, col)));
+        }
+    }
+
+    fn make_token(
+        &mut self,
+        token_type: TokenType,
+        start: usize,
+        line: usize,
+        col: usize,
+    ) -> Token {
+        Token::new(
+            token_type,
+            mem::take(&mut self.current),
+            line as u32,
+            col as u32,
+            Span::new(start, self.offset),
+        )
+    }
+
+    /// Skips a `/* ... */` comment, which may contain nested block comments.
+    fn handle_block_comment(&mut self, line_num: usize) -> LoxResult<()> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                Some('/') if self.advance_if('*') => depth += 1,
+                Some('*') if self.advance_if('/') => depth -= 1,
+                Some(_) => {}
+                None => return Err(LexingError::UnterminatedComment { line_num })?,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_string(&mut self) -> LoxResult<TokenType> {
+        let mut string_lit = String::new();
+        // Keep scanning to the closing quote after a bad escape so lexing can
+        // resume after the string.
+        let mut escape_err = None;
+        loop {
+            match self.advance() {
+                Some('"') => {
+                    return match escape_err {
+                        Some(err) => Err(err)?,
+                        None => Ok(String_(string_lit)),
                     }
                 }
-                (first @ '0'...'9', second) => handle_number(
-                    &mut tokens,
-                    &mut letters,
-                    make_token,
-                    (idx, (first, second)),
-                    line_num,
-                )?,
-                (lett @ 'a'...'z', _) | (lett @ 'A'...'Z', _) => {
-                    handle_ident_or_keyword(&mut tokens, &mut letters, make_token, lett)?
+                Some('\\') => match self.handle_escape() {
+                    Ok(chr) => string_lit.push(chr),
+                    Err(err) => {
+                        escape_err.get_or_insert(err);
+                    }
+                },
+                Some(chr) => string_lit.push(chr),
+                None => {
+                    return Err(LexingError::UnexpectedEndStringLiteral {
+                        line_num: self.line_num,
+                    })?
                 }
-                (first, _) => return Err(LexingError::InvalidToken(first))?,
             }
         }
     }
-    Ok(tokens)
-}
 
-#[inline]
-fn handle_ident_or_keyword(
-    tokens: &mut Vec<Token>,
-    letters: &mut impl Iterator<Item = (usize, (char, char))>,
-    make_token: impl Fn(TokenType, usize) -> Token,
-    lett: char,
-) -> LoxResult<()> {
-    let mut identifier_lit = vec![lett];
-    loop {
-        let next_letter: Option<(usize, (char, char))> = letters.next();
-        match next_letter {
-            Some((_, (first, _))) => match first {
-                'a'...'z' | 'A'...'Z' | '0'...'9' => identifier_lit.push(first),
-                _ => {
-                    tokens.push(make_ident_or_keyword(&identifier_lit, &make_token));
-                    break;
-                }
-            },
+    /// Decodes the escape sequence following a backslash in a string literal.
+    fn handle_escape(&mut self) -> Result<char, LexingError> {
+        let line_num = self.line_num;
+        let escape = match self.peek_char() {
+            Some(chr) => {
+                self.advance();
+                chr
+            }
             None => {
-                tokens.push(make_ident_or_keyword(&identifier_lit, &make_token));
-                break;
+                return Err(LexingError::InvalidEscape {
+                    line_num,
+                    escape: String::new(),
+                })
             }
         };
+        match escape {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            'u' => self.handle_unicode_escape(),
+            other => Err(LexingError::InvalidEscape {
+                line_num,
+                escape: other.to_string(),
+            }),
+        }
+    }
+
+    /// Decodes the `{XXXX}` part of a `\u{XXXX}` escape.
+    fn handle_unicode_escape(&mut self) -> Result<char, LexingError> {
+        let line_num = self.line_num;
+        let mut escape = String::from("u");
+        if self.advance_if('{') {
+            escape.push('{');
+            while let Some(digit) = self.peek_char().filter(char::is_ascii_hexdigit) {
+                self.advance();
+                escape.push(digit);
+            }
+            if self.advance_if('}') {
+                escape.push('}');
+                let code = u32::from_str_radix(&escape[2..escape.len() - 1], 16).ok();
+                if let Some(chr) = code.and_then(std::char::from_u32) {
+                    return Ok(chr);
+                }
+            }
+        }
+        Err(LexingError::InvalidEscape { line_num, escape })
     }
-    Ok(())
-}
 
-#[inline]
-fn make_ident_or_keyword(
-    identifier_lit: &[char],
-    make_token: impl Fn(TokenType, usize) -> Token,
-) -> Token {
-    use crate::token::RESERVED_TOKENS;
+    fn handle_ident_or_keyword(&mut self) -> TokenType {
+        use crate::token::RESERVED_TOKENS;
 
-    let identifier: String = identifier_lit.iter().collect();
-    if let Some(reserved) = RESERVED_TOKENS.get(identifier.as_str()) {
-        make_token(reserved.clone(), identifier_lit.len())
-    } else {
-        make_token(Identifier(identifier), identifier_lit.len())
+        while self.peek_char().is_some_and(is_ident_continue) {
+            self.advance();
+        }
+        match RESERVED_TOKENS.get(self.current.as_str()) {
+            Some(reserved) => reserved.clone(),
+            None => Identifier(self.current.clone()),
+        }
     }
-}
 
-#[inline]
-fn handle_number(
-    tokens: &mut Vec<Token>,
-    letters: &mut impl Iterator<Item = (usize, (char, char))>,
-    make_token: impl Fn(TokenType, usize) -> Token,
-    lett: (usize, (char, char)),
-    line_num: usize,
-) -> LoxResult<()> {
-    let mut num_lit: Vec<char> = vec![];
-    let mut next_letter: Option<(usize, (char, char))> = Some(lett);
-    loop {
-        match next_letter {
-            Some((_idx, (chr, next))) if !next.is_alphanumeric() && next != '.' => {
-                num_lit.push(chr);
-                tokens.push(make_number(&num_lit, make_token, line_num)?);
-                break;
+    fn handle_number(&mut self, first: char) -> LoxResult<TokenType> {
+        let radix = match (first, self.peek_char()) {
+            ('0', Some('x')) | ('0', Some('X')) => 16,
+            ('0', Some('b')) | ('0', Some('B')) => 2,
+            _ => 10,
+        };
+        let value = if radix == 10 {
+            let mut literal = first.to_string();
+            literal += &self.consume_digits(10)?;
+            // A trailing dot isn't part of the number, e.g. `123.` or `123.foo`
+            if self.peek_char() == Some('.')
+                && self
+                    .peek_char_nth(1)
+                    .is_some_and(|chr| chr.is_ascii_digit())
+            {
+                self.advance();
+                literal.push('.');
+                literal += &self.consume_digits(10)?;
             }
-            Some((_idx, (chr, _))) => {
-                num_lit.push(chr);
+            if let Some('e') | Some('E') = self.peek_char() {
+                self.advance();
+                literal.push('e');
+                if let Some(sign @ '+') | Some(sign @ '-') = self.peek_char() {
+                    self.advance();
+                    literal.push(sign);
+                }
+                let exponent = self.consume_digits(10)?;
+                if exponent.is_empty() {
+                    return Err(self.invalid_digit("expected digits in exponent".into()));
+                }
+                literal += &exponent;
             }
-            None => {
-                tokens.push(make_number(&num_lit, make_token, line_num)?);
-                break;
+            literal
+                .parse()
+                .expect("Number literal was already validated")
+        } else {
+            let prefix = self.advance().map(|chr| format!("0{}", chr));
+            let digits = self.consume_digits(radix)?;
+            if digits.is_empty() {
+                return Err(self.invalid_digit(format!(
+                    "expected digits after '{}'",
+                    prefix.unwrap_or_default()
+                )));
             }
+            digits.chars().fold(0.0, |acc, digit| {
+                acc * f64::from(radix) + f64::from(digit.to_digit(radix).unwrap())
+            })
+        };
+        match self.peek_char() {
+            Some(chr) if chr.is_alphanumeric() || chr == '_' => {
+                Err(self.invalid_digit(format!("unexpected '{}' in number literal", chr)))
+            }
+            _ => Ok(Number(value)),
+        }
+    }
+
+    /// Consumes a run of digits in `radix`, which may be separated by single
+    /// underscores. Returns the digits without the underscores.
+    fn consume_digits(&mut self, radix: u32) -> LoxResult<String> {
+        let mut digits = String::new();
+        loop {
+            match self.peek_char() {
+                Some(chr) if chr.is_digit(radix) => {
+                    self.advance();
+                    digits.push(chr);
+                }
+                Some('_') if self.peek_char_nth(1).is_some_and(|chr| chr.is_digit(radix)) => {
+                    self.advance();
+                }
+                Some('_') => {
+                    return Err(self.invalid_digit("'_' must be followed by a digit".into()))
+                }
+                _ => return Ok(digits),
+            }
+        }
+    }
+
+    /// Builds an `InvalidDigit` error pointing at the next character, skipping
+    /// the rest of the malformed literal so lexing can resume after it.
+    fn invalid_digit(&mut self, reason: String) -> LoxError {
+        let line_num = self.line_num;
+        let start = self.offset;
+        let end = start + self.peek_char().map_or(0, char::len_utf8);
+        while self
+            .peek_char()
+            .is_some_and(|chr| chr.is_alphanumeric() || chr == '_')
+        {
+            self.advance();
+        }
+        LexingError::InvalidDigit {
+            line_num,
+            span: Span::new(start, end),
+            reason,
         }
-        next_letter = letters.next()
+        .into()
     }
-    Ok(())
 }
 
-#[inline]
-fn make_number(
-    num_lit: &[char],
-    make_token: impl Fn(TokenType, usize) -> Token,
-    line_num: usize,
-) -> LoxResult<Token> {
-    let num: String = num_lit.iter().collect();
-    Ok(make_token(
-        Number(
-            num.parse()
-                .map_err(|err| LexingError::InvalidDigit { line_num, err })?,
-        ),
-        num_lit.len(),
-    ))
+/// ALPHA from the Lox grammar: `'a'...'z' | 'A'...'Z' | '_'`.
+fn is_ident_start(chr: char) -> bool {
+    chr.is_ascii_alphabetic() || chr == '_' || (!chr.is_ascii() && is_unicode_ident_start(chr))
+}
+
+/// ALPHA | DIGIT from the Lox grammar.
+fn is_ident_continue(chr: char) -> bool {
+    chr.is_ascii_alphanumeric() || chr == '_' || (!chr.is_ascii() && is_unicode_ident_continue(chr))
+}
+
+#[cfg(feature = "unicode-idents")]
+fn is_unicode_ident_start(chr: char) -> bool {
+    UnicodeXID::is_xid_start(chr)
+}
+
+#[cfg(feature = "unicode-idents")]
+fn is_unicode_ident_continue(chr: char) -> bool {
+    UnicodeXID::is_xid_continue(chr)
+}
+
+#[cfg(not(feature = "unicode-idents"))]
+fn is_unicode_ident_start(_chr: char) -> bool {
+    false
+}
+
+#[cfg(not(feature = "unicode-idents"))]
+fn is_unicode_ident_continue(_chr: char) -> bool {
+    false
+}
+
+impl<I: Iterator<Item = char>> Iterator for Lexer<I> {
+    type Item = LoxResult<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lookahead.pop_front().or_else(|| self.scan_token())
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use std::io::Cursor;
-
-    use crate::error::LoxError;
+    use proptest::prelude::*;
 
     use super::*;
 
@@ -207,7 +456,10 @@ mod test {
             .iter()
             .for_each(|&text| {
                 let res = scan_tokens(text).unwrap();
-                assert_eq!(&res[3], &Token::new(Number(123f64), "123".into(), 0));
+                assert_eq!(
+                    &res[3],
+                    &Token::new(Number(123f64), "123".into(), 1, 11, Span::new(10, 13))
+                );
             })
     }
 
@@ -216,10 +468,10 @@ mod test {
         let example = r#"var foo = 123f456"#;
         let res = scan_tokens(example);
         match res.expect_err("Should have failed to parse invalid number") {
-            LoxError::InnerLexingError(LexingError::InvalidDigit {
-                line_num: _,
-                err: _,
-            }) => {}
+            LoxError::InnerLexingError(LexingError::InvalidDigit { line_num, span, .. }) => {
+                assert_eq!(line_num, 1);
+                assert_eq!(span, Span::new(13, 14));
+            }
             err => panic!("Wrong error type {:?}. Expected Invalid Digit.", err),
         }
     }
@@ -231,12 +483,24 @@ mod test {
 
         assert_eq!(
             &res[0],
-            &Token::new(String_("asdf".into()), r#""asdf""#.into(), 0)
+            &Token::new(
+                String_("asdf".into()),
+                r#""asdf""#.into(),
+                1,
+                1,
+                Span::new(0, 6)
+            )
         );
 
-        assert_eq!(&res[1], &Token::new(EqualEqual, "==".into(), 0));
+        assert_eq!(
+            &res[1],
+            &Token::new(EqualEqual, "==".into(), 1, 8, Span::new(7, 9))
+        );
 
-        assert_eq!(&res[2], &Token::new(Number(123.456), "123.456".into(), 0));
+        assert_eq!(
+            &res[2],
+            &Token::new(Number(123.456), "123.456".into(), 1, 11, Span::new(10, 17))
+        );
     }
 
     #[test]
@@ -244,7 +508,10 @@ mod test {
         let example = "\n123.456";
         let res = scan_tokens(example).unwrap();
 
-        assert_eq!(&res[0], &Token::new(Number(123.456), "123.456".into(), 1));
+        assert_eq!(
+            &res[0],
+            &Token::new(Number(123.456), "123.456".into(), 2, 1, Span::new(1, 8))
+        );
     }
 
     #[test]
@@ -252,16 +519,31 @@ mod test {
         let example = "var foobar = 123.456";
         let res = scan_tokens(example).unwrap();
 
-        assert_eq!(&res[0], &Token::new(Var, "var".into(), 0));
+        assert_eq!(
+            &res[0],
+            &Token::new(Var, "var".into(), 1, 1, Span::new(0, 3))
+        );
 
         assert_eq!(
             &res[1],
-            &Token::new(Identifier("foobar".into()), "foobar".into(), 0)
+            &Token::new(
+                Identifier("foobar".into()),
+                "foobar".into(),
+                1,
+                5,
+                Span::new(4, 10)
+            )
         );
 
-        assert_eq!(&res[2], &Token::new(Equal, "=".into(), 0));
+        assert_eq!(
+            &res[2],
+            &Token::new(Equal, "=".into(), 1, 12, Span::new(11, 12))
+        );
 
-        assert_eq!(&res[3], &Token::new(Number(123.456), "123.456".into(), 0));
+        assert_eq!(
+            &res[3],
+            &Token::new(Number(123.456), "123.456".into(), 1, 14, Span::new(13, 20))
+        );
     }
 
     #[test]
@@ -269,6 +551,392 @@ mod test {
         let example = "//HIII THEREEEEE\nvar";
         let res = scan_tokens(example).unwrap();
 
-        assert_eq!(&res[0], &Token::new(Var, "var".into(), 1));
+        assert_eq!(
+            &res[0],
+            &Token::new(Var, "var".into(), 2, 1, Span::new(17, 20))
+        );
+    }
+
+    #[test]
+    fn test_ends_with_eof() {
+        let res = scan_tokens("1").unwrap();
+
+        assert_eq!(res.len(), 2);
+        assert_eq!(&res[1], &Token::new(Eof, "".into(), 1, 2, Span::new(1, 1)));
+    }
+
+    #[test]
+    fn test_identifier_before_paren() {
+        let res = scan_tokens("(foo)").unwrap();
+        let types: Vec<_> = res.into_iter().map(|token| token.token_type).collect();
+
+        assert_eq!(
+            types,
+            vec![LeftParen, Identifier("foo".into()), RightParen, Eof]
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_operators() {
+        let res = scan_tokens("* ** % ~/ //~/").unwrap();
+        let types: Vec<_> = res.into_iter().map(|token| token.token_type).collect();
+
+        assert_eq!(types, vec![Star, StarStar, Percent, TildeSlash, Eof]);
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        let res = scan_tokens("& | ^ ~ ~/ << <= < >> >= >").unwrap();
+        let types: Vec<_> = res.into_iter().map(|token| token.token_type).collect();
+
+        assert_eq!(
+            types,
+            vec![
+                Ampersand,
+                Pipe,
+                Caret,
+                Tilde,
+                TildeSlash,
+                LessLess,
+                LessEqual,
+                Less,
+                GreaterGreater,
+                GreaterEqual,
+                Greater,
+                Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nul_byte_is_invalid_token() {
+        match scan_tokens("1\n \0 2").expect_err("NUL is not a valid token") {
+            LoxError::InnerLexingError(LexingError::InvalidToken {
+                line_num: 2,
+                span,
+                token: '\0',
+            }) => assert_eq!((span.start, span.end), (3, 4)),
+            err => panic!("Wrong error type {:?}. Expected Invalid Token.", err),
+        }
+    }
+
+    #[test]
+    fn test_peek_does_not_consume() {
+        let mut lexer = Lexer::new("var x".chars());
+
+        assert_eq!(lexer.peek_nth(1).unwrap().as_ref().unwrap().lexeme, "x");
+        assert_eq!(lexer.peek().unwrap().as_ref().unwrap().token_type, Var);
+        assert_eq!(lexer.next().unwrap().unwrap().token_type, Var);
+        assert_eq!(lexer.next().unwrap().unwrap().lexeme, "x");
+        assert_eq!(lexer.next().unwrap().unwrap().token_type, Eof);
+        assert!(lexer.next().is_none());
+        assert!(lexer.peek().is_none());
+    }
+
+    #[test]
+    fn test_reads_input_lazily() {
+        let consumed = std::cell::Cell::new(0);
+        let chars = "1 + 2 + 3"
+            .chars()
+            .inspect(|_| consumed.set(consumed.get() + 1));
+        let mut lexer = Lexer::new(chars);
+
+        lexer.next().unwrap().unwrap();
+        assert_eq!(consumed.get(), 2);
+    }
+
+    #[test]
+    fn test_non_ascii_offsets() {
+        let example = "\"héllo wörld\" + 1";
+        let res = scan_tokens(example).unwrap();
+
+        assert_eq!(
+            &res[0],
+            &Token::new(
+                String_("héllo wörld".into()),
+                "\"héllo wörld\"".into(),
+                1,
+                1,
+                Span::new(0, 15)
+            )
+        );
+        assert_eq!(
+            &res[1],
+            &Token::new(Plus, "+".into(), 1, 15, Span::new(16, 17))
+        );
+        assert_eq!(&example[res[2].span.start..res[2].span.end], "1");
+    }
+
+    #[test]
+    fn test_non_ascii_string_contents() {
+        let res = scan_tokens("\"日本語 🦀\"").unwrap();
+
+        assert_eq!(res[0].token_type, String_("日本語 🦀".into()));
+    }
+
+    #[cfg(feature = "unicode-idents")]
+    #[test]
+    fn test_unicode_identifier() {
+        let res = scan_tokens("var größe = 1;").unwrap();
+
+        assert_eq!(
+            &res[1],
+            &Token::new(
+                Identifier("größe".into()),
+                "größe".into(),
+                1,
+                5,
+                Span::new(4, 11)
+            )
+        );
+        assert_eq!(res[2].col, 11);
+    }
+
+    #[test]
+    fn test_non_identifier_symbol() {
+        match scan_tokens("1 → 2").expect_err("Arrow is not a valid token") {
+            LoxError::InnerLexingError(LexingError::InvalidToken {
+                line_num: 1,
+                span,
+                token: '→',
+            }) => assert_eq!((span.start, span.end), (2, 5)),
+            err => panic!("Wrong error type {:?}. Expected Invalid Token.", err),
+        }
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let res = scan_tokens(r#""a\n\tb \"q\" \\ \r\u{48}\u{1F980}""#).unwrap();
+
+        assert_eq!(
+            res[0].token_type,
+            String_("a\n\tb \"q\" \\ \r\u{48}\u{1F980}".into())
+        );
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        for (example, expected) in &[
+            (r#""\q""#, "q"),
+            (r#""\u41""#, "u"),
+            (r#""\u{}""#, "u{}"),
+            (r#""\u{zz}""#, "u{"),
+            (r#""\u{D800}""#, "u{D800}"),
+            (r#""\u{110000}""#, "u{110000}"),
+        ] {
+            match scan_tokens(example).expect_err("Should have failed on invalid escape") {
+                LoxError::InnerLexingError(LexingError::InvalidEscape { escape, .. }) => {
+                    assert_eq!(&escape, expected)
+                }
+                err => panic!("Wrong error type {:?}. Expected Invalid Escape.", err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_lexing_resumes_after_invalid_escape() {
+        let mut lexer = Lexer::new(r#""\q" 1"#.chars());
+
+        assert!(lexer.next().unwrap().is_err());
+        assert_eq!(lexer.next().unwrap().unwrap().token_type, Number(1.0));
+    }
+
+    #[test]
+    fn test_multi_line_string_line_number() {
+        let example = "\"one\ntwo\nthree\" x\ny";
+        let res = scan_tokens(example).unwrap();
+
+        assert_eq!(res[0].token_type, String_("one\ntwo\nthree".into()));
+        assert_eq!(res[0].line, 1);
+        assert_eq!((res[1].line, res[1].col), (3, 8));
+        assert_eq!((res[2].line, res[2].col), (4, 1));
+    }
+
+    #[test]
+    fn test_unterminated_multi_line_string() {
+        match scan_tokens("\"one\ntwo").expect_err("Should have failed on unterminated string") {
+            LoxError::InnerLexingError(LexingError::UnexpectedEndStringLiteral { line_num }) => {
+                assert_eq!(line_num, 2)
+            }
+            err => panic!("Wrong error type {:?}. Expected Unexpected End.", err),
+        }
+    }
+
+    #[test]
+    fn test_line_comment_at_eof() {
+        let res = scan_tokens("var // trailing comment").unwrap();
+
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[1].token_type, Eof);
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let example = "1 /* one\n /* nested\n */ still comment */ + /**/ 2 /* * / */";
+        let res = scan_tokens(example).unwrap();
+        let types: Vec<_> = res.iter().map(|token| token.token_type.clone()).collect();
+
+        assert_eq!(types, vec![Number(1.0), Plus, Number(2.0), Eof]);
+        assert_eq!(res[1].line, 3);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        match scan_tokens("1\n/* outer /* inner */\n").expect_err("Comment was never closed") {
+            LoxError::InnerLexingError(LexingError::UnterminatedComment { line_num }) => {
+                assert_eq!(line_num, 2)
+            }
+            err => panic!("Wrong error type {:?}. Expected Unterminated Comment.", err),
+        }
+    }
+
+    #[test]
+    fn test_extended_numbers() {
+        for (example, expected) in &[
+            ("1_000_000", 1_000_000f64),
+            ("0xFF", 255f64),
+            ("0Xdead_beef", 3_735_928_559f64),
+            ("0b1010", 10f64),
+            ("0B1111_0000", 240f64),
+            ("1e-9", 1e-9),
+            ("2.5E+3", 2500f64),
+            ("6e2", 600f64),
+            ("1_0.2_5", 10.25),
+            ("007", 7f64),
+        ] {
+            let res = scan_tokens(example).unwrap();
+            assert_eq!(res[0].token_type, Number(*expected), "{}", example);
+            assert_eq!(res[0].lexeme, *example);
+            assert_eq!(res[1].token_type, Eof);
+        }
+    }
+
+    #[test]
+    fn test_trailing_dot_is_not_part_of_number() {
+        let types: Vec<_> = scan_tokens("123. 4.foo")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(
+            types,
+            vec![
+                Number(123.0),
+                Dot,
+                Number(4.0),
+                Dot,
+                Identifier("foo".into()),
+                Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_number_spans() {
+        for (example, expected) in &[
+            ("1__0", Span::new(1, 2)),
+            ("1_", Span::new(1, 2)),
+            ("0x", Span::new(2, 2)),
+            ("0xFG", Span::new(3, 4)),
+            ("0b102", Span::new(4, 5)),
+            ("1e", Span::new(2, 2)),
+            ("1e+x", Span::new(3, 4)),
+            ("12ab", Span::new(2, 3)),
+        ] {
+            match scan_tokens(example).expect_err(example) {
+                LoxError::InnerLexingError(LexingError::InvalidDigit { span, .. }) => {
+                    assert_eq!(span, *expected, "{}", example)
+                }
+                err => panic!("Wrong error type {:?}. Expected Invalid Digit.", err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_lexing_resumes_after_invalid_number() {
+        let mut lexer = Lexer::new("0xZZ + 1".chars());
+
+        assert!(lexer.next().unwrap().is_err());
+        assert_eq!(lexer.next().unwrap().unwrap().token_type, Plus);
+    }
+
+    #[test]
+    fn test_identifier_corpus() {
+        for ident in &[
+            "a",
+            "_",
+            "__",
+            "my_var",
+            "_private",
+            "snake_case_name",
+            "trailing_",
+            "with_123_digits",
+            "x1",
+            "_0",
+            "CamelCase",
+            "SCREAMING_SNAKE",
+            "var_",
+            "_var",
+            "classy",
+            "andor",
+            "nil_or_value",
+        ] {
+            let res = scan_tokens(ident).unwrap();
+            assert_eq!(
+                res[0].token_type,
+                Identifier(ident.to_string()),
+                "{}",
+                ident
+            );
+            assert_eq!(res[0].lexeme, *ident);
+            assert_eq!(res[1].token_type, Eof, "{}", ident);
+        }
+    }
+
+    #[test]
+    fn test_snake_case_statement() {
+        let types: Vec<_> = scan_tokens("var my_var=_other_1;")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(
+            types,
+            vec![
+                Var,
+                Identifier("my_var".into()),
+                Equal,
+                Identifier("_other_1".into()),
+                Semicolon,
+                Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identifier_cannot_start_with_digit() {
+        match scan_tokens("1st_place").expect_err("Identifiers can't start with a digit") {
+            LoxError::InnerLexingError(LexingError::InvalidDigit { span, .. }) => {
+                assert_eq!(span, Span::new(1, 2))
+            }
+            err => panic!("Wrong error type {:?}. Expected Invalid Digit.", err),
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_lexing_never_panics(text in any::<String>()) {
+            let _ = scan_tokens(&text);
+        }
+
+        #[test]
+        fn prop_spans_are_char_boundaries(text in "[ -~\u{80}-\u{10ffff}\\n]*") {
+            for token in Lexer::new(text.chars()).take_while(Result::is_ok) {
+                let token = token.unwrap();
+                prop_assert_eq!(&text[token.span.start..token.span.end], token.lexeme.as_str());
+            }
+        }
     }
 }
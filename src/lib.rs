@@ -0,0 +1,349 @@
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use crate::ast_interpreter::AstIntepreter;
+use crate::native::{Arity, Context};
+
+pub use crate::ast::Expr;
+pub use crate::ast_interpreter::{DEFAULT_MAX_CALL_DEPTH, STACK_SIZE};
+pub use crate::error::{LoxError, LoxResult};
+pub use crate::system::Capabilities;
+pub use crate::token::Token;
+pub use crate::types::LoxType;
+
+pub mod ast;
+mod ast_interpreter;
+pub mod error;
+mod interpreter;
+pub mod lexer;
+mod lists;
+mod maps;
+mod math;
+pub mod native;
+pub mod parser;
+mod prelude;
+pub mod printer;
+#[cfg(feature = "repl")]
+pub mod repl;
+mod stack;
+mod strings;
+mod system;
+#[cfg(test)]
+mod test_util;
+pub mod token;
+pub mod types;
+
+/// An embeddable Lox engine. Globals persist across calls until `reset`.
+///
+/// Lox calls recurse on the Rust stack, so deeply recursive scripts need a
+/// thread with a big stack, such as `STACK_SIZE`, to reach the call depth
+/// limit rather than overflowing.
+///
+/// Globals are released on `reset` and when the engine is dropped, after which
+/// Lox functions taken out with `get_global` no longer see them. The variables
+/// of a call or block live as long as functions declared in it, except that
+/// storing such a function in a variable it can see makes a reference cycle,
+/// freed only by `reset` or dropping the engine.
+#[derive(Default)]
+pub struct Lox<'a> {
+    vm: AstIntepreter<'a>,
+}
+
+impl<'a> Lox<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends the output of `print` to `output` instead of stdout.
+    pub fn set_output(&mut self, output: impl Write + 'a) {
+        self.vm.set_output(output);
+    }
+
+    /// Makes `input()` read from `input` instead of stdin.
+    pub fn set_input(&mut self, input: impl BufRead + 'a) {
+        self.vm.set_input(input);
+    }
+
+    /// Grants scripts access to the filesystem or environment, which they
+    /// don't have by default.
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.vm.set_capabilities(capabilities);
+    }
+
+    /// Sets what `args()` returns to scripts, e.g. the command line
+    /// arguments after the script's path.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.vm.set_args(args);
+    }
+
+    /// Sets how many Lox calls may be nested before a script fails with a
+    /// stack overflow, `DEFAULT_MAX_CALL_DEPTH` unless changed.
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.vm.set_max_call_depth(max_call_depth);
+    }
+
+    /// Makes `random()` produce the same sequence on every run with `seed`.
+    pub fn seed_random(&mut self, seed: u64) {
+        self.vm.seed_random(seed);
+    }
+
+    /// Evaluates `source`, returning its value if it is a single expression.
+    /// Anything else runs as a program and returns `None`.
+    pub fn eval_str(&mut self, source: &str) -> LoxResult<Option<LoxType>> {
+        self.vm.eval_str(source)
+    }
+
+    /// Runs `source` as a program.
+    pub fn run_str(&mut self, source: &str) -> LoxResult<()> {
+        self.vm.run(source)
+    }
+
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> LoxResult<()> {
+        self.run_str(&fs::read_to_string(path)?)
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<LoxType> {
+        self.vm.get_global(name)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: LoxType) {
+        self.vm.globals().define(name, value);
+    }
+
+    /// Registers a global function implemented in Rust, callable from Lox
+    /// like any `fun`. Arguments are checked against `arity` before the call.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Context, &[LoxType]) -> LoxResult<LoxType> + 'static,
+    ) {
+        self.vm.define_native(name, arity, function);
+    }
+
+    /// All global variables, sorted by name. Builtins aren't included.
+    pub fn globals(&self) -> Vec<(String, LoxType)> {
+        self.vm.global_bindings()
+    }
+
+    /// Discards all globals, starting over with only the builtins.
+    pub fn reset(&mut self) {
+        self.vm.reset();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::env;
+    use std::rc::Rc;
+
+    use crate::native::NativeObject;
+    use crate::test_util::assert_runtime_errors;
+
+    use super::*;
+
+    #[test]
+    fn test_eval_str() {
+        let mut lox = Lox::new();
+
+        assert_eq!(lox.eval_str("1 + 2").unwrap(), Some(LoxType::Number(3.0)));
+        assert_eq!(lox.eval_str("var a = 1;").unwrap(), None);
+        assert_eq!(lox.eval_str("a").unwrap(), Some(LoxType::Number(1.0)));
+        match lox.eval_str("-nil") {
+            Err(LoxError::InnerRuntimeError(_)) => {}
+            res => panic!("Expected a runtime error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut lox = Lox::new();
+        lox.set_global("name", LoxType::String_("lox".into()));
+        lox.run_str("var greeting = \"hi \" == name;").unwrap();
+
+        assert_eq!(lox.get_global("greeting"), Some(LoxType::Boolean(false)));
+        assert_eq!(lox.get_global("missing"), None);
+        lox.reset();
+        assert_eq!(lox.get_global("name"), None);
+    }
+
+    #[test]
+    fn test_run_file_with_output() {
+        let path = env::temp_dir().join(format!("lox_lib_run_{}.lox", std::process::id()));
+        fs::write(&path, "var x = 2;\nprint x * 21;\n").unwrap();
+        let mut out = Vec::new();
+        {
+            let mut lox = Lox::new();
+            lox.set_output(&mut out);
+            lox.run_file(&path).unwrap();
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "42\n");
+        assert!(Lox::new().run_file("/nonexistent/file.lox").is_err());
+    }
+
+    #[test]
+    fn test_native_functions() {
+        let mut lox = Lox::new();
+        lox.define_native("sum", Arity::Variadic, |ctx, args| {
+            let mut total = 0.0;
+            for arg in args {
+                match arg {
+                    LoxType::Number(num) => total += num,
+                    _ => return Err(ctx.error("sum() takes numbers.")),
+                }
+            }
+            Ok(LoxType::Number(total))
+        });
+        lox.define_native("twice", Arity::Fixed(2), |ctx, args| {
+            let once = ctx.call(&args[0], &[args[1].clone()])?;
+            ctx.call(&args[0], &[once])
+        });
+        lox.run_str("fun inc(n) { return n + 1; }").unwrap();
+
+        assert_eq!(lox.eval_str("sum()").unwrap(), Some(LoxType::Number(0.0)));
+        assert_eq!(
+            lox.eval_str("sum(1, 2, 3)").unwrap(),
+            Some(LoxType::Number(6.0))
+        );
+        assert_eq!(
+            lox.eval_str("twice(inc, 1)").unwrap(),
+            Some(LoxType::Number(3.0))
+        );
+        assert_eq!(
+            lox.eval_str("twice(sum, 2)").unwrap(),
+            Some(LoxType::Number(2.0))
+        );
+        assert_eq!(lox.get_global("sum").unwrap().to_string(), "<native fn>");
+        assert_runtime_errors(&mut lox, &["sum(1, nil)", "twice(inc)", "twice(1, 2)"]);
+    }
+
+    struct Counter {
+        count: Cell<f64>,
+        step: Cell<f64>,
+    }
+
+    impl NativeObject for Counter {
+        fn class_name(&self) -> &str {
+            "Counter"
+        }
+
+        fn get_property(&self, name: &str) -> Option<LoxType> {
+            match name {
+                "count" => Some(LoxType::Number(self.count.get())),
+                "step" => Some(LoxType::Number(self.step.get())),
+                _ => None,
+            }
+        }
+
+        fn set_property(&self, ctx: &mut Context, name: &str, value: LoxType) -> LoxResult<()> {
+            match (name, value) {
+                ("step", LoxType::Number(step)) => {
+                    self.step.set(step);
+                    Ok(())
+                }
+                _ => Err(ctx.error(format!("Can't set '{}'.", name))),
+            }
+        }
+
+        fn has_method(&self, name: &str) -> bool {
+            name == "increment"
+        }
+
+        fn call_method(
+            &self,
+            ctx: &mut Context,
+            name: &str,
+            args: &[LoxType],
+        ) -> LoxResult<LoxType> {
+            match (name, args) {
+                ("increment", []) => {
+                    self.count.set(self.count.get() + self.step.get());
+                    Ok(LoxType::Number(self.count.get()))
+                }
+                _ => Err(ctx.error(format!("Bad call to '{}'.", name))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_native_objects() {
+        let counter = Rc::new(Counter {
+            count: Cell::new(0.0),
+            step: Cell::new(1.0),
+        });
+        let mut lox = Lox::new();
+        lox.set_global("counter", LoxType::NativeObject(counter.clone()));
+        lox.run_str("counter.increment(); counter.step = 5; var inc = counter.increment; inc();")
+            .unwrap();
+
+        assert_eq!(counter.count.get(), 6.0);
+        assert_eq!(
+            lox.eval_str("counter.count").unwrap(),
+            Some(LoxType::Number(6.0))
+        );
+        assert_eq!(
+            lox.eval_str("counter").unwrap().unwrap().to_string(),
+            "Counter instance"
+        );
+        assert_eq!(
+            lox.eval_str("counter == counter").unwrap(),
+            Some(LoxType::Boolean(true))
+        );
+        assert_runtime_errors(
+            &mut lox,
+            &[
+                "counter.missing",
+                "counter.count = 1",
+                "counter.increment(1)",
+                "nil.count",
+                "1.5.x = 2",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_globals_are_freed() {
+        let counter = Rc::new(Counter {
+            count: Cell::new(0.0),
+            step: Cell::new(1.0),
+        });
+        let mut lox = Lox::new();
+        lox.set_global("counter", LoxType::NativeObject(counter.clone()));
+        lox.run_str("fun increment() { return counter.increment(); } increment();")
+            .unwrap();
+        lox.reset();
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        lox.set_global("counter", LoxType::NativeObject(counter.clone()));
+        lox.run_str("fun increment() { return counter.increment(); } increment();")
+            .unwrap();
+        drop(lox);
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert_eq!(counter.count.get(), 2.0);
+    }
+
+    #[test]
+    fn test_closures_are_freed() {
+        let counter = Rc::new(Counter {
+            count: Cell::new(0.0),
+            step: Cell::new(1.0),
+        });
+        let mut lox = Lox::new();
+        lox.set_global("counter", LoxType::NativeObject(counter.clone()));
+        lox.run_str(
+            "fun make() { var c = counter; fun get() { return c; } return get; }
+             var get = make();
+             get().increment();
+             get = nil;
+             { var c = counter; fun get() { return c; } get().increment(); }",
+        )
+        .unwrap();
+        lox.set_global("counter", LoxType::Nil);
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert_eq!(counter.count.get(), 2.0);
+    }
+}
@@ -0,0 +1,142 @@
+//! Methods available on list values, e.g. `list.push(1)`.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::error::LoxResult;
+use crate::native::{bound_method, index_arg, Context, Method};
+use crate::types::LoxType;
+
+type List = Rc<RefCell<Vec<LoxType>>>;
+
+/// Looks up `name` on list `receiver`, bound to it as a function value.
+pub fn method(receiver: &List, name: &str) -> Option<LoxType> {
+    let (arity, method): (usize, Method<List>) = match name {
+        "push" => (1, push),
+        "pop" => (0, pop),
+        "len" => (0, |_, list, _| {
+            Ok(LoxType::Number(list.borrow().len() as f64))
+        }),
+        "insert" => (2, insert),
+        "remove" => (1, remove),
+        "slice" => (2, slice),
+        _ => return None,
+    };
+    Some(bound_method(receiver.clone(), name, arity, method))
+}
+
+fn push(_: &mut Context, list: &List, args: &[LoxType]) -> LoxResult<LoxType> {
+    list.borrow_mut().push(args[0].clone());
+    Ok(LoxType::Nil)
+}
+
+/// Removes and returns the last element.
+fn pop(ctx: &mut Context, list: &List, _: &[LoxType]) -> LoxResult<LoxType> {
+    match list.borrow_mut().pop() {
+        Some(value) => Ok(value),
+        None => Err(ctx.error("pop() can't pop from an empty list.")),
+    }
+}
+
+/// Inserts the second argument before the element at the first.
+fn insert(ctx: &mut Context, list: &List, args: &[LoxType]) -> LoxResult<LoxType> {
+    let idx = index_arg(ctx, "insert", &args[0], list.borrow().len(), true)?;
+    list.borrow_mut().insert(idx, args[1].clone());
+    Ok(LoxType::Nil)
+}
+
+/// Removes and returns the element at the argument.
+fn remove(ctx: &mut Context, list: &List, args: &[LoxType]) -> LoxResult<LoxType> {
+    let idx = index_arg(ctx, "remove", &args[0], list.borrow().len(), false)?;
+    Ok(list.borrow_mut().remove(idx))
+}
+
+/// A new list of the elements from `start` up to, not including, `end`.
+fn slice(ctx: &mut Context, list: &List, args: &[LoxType]) -> LoxResult<LoxType> {
+    let elements = list.borrow();
+    let start = index_arg(ctx, "slice", &args[0], elements.len(), true)?;
+    let end = index_arg(ctx, "slice", &args[1], elements.len(), true)?;
+    if start > end {
+        return Err(ctx.error(format!("slice() start {} is after end {}.", start, end)));
+    }
+    Ok(LoxType::list(elements[start..end].to_vec()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::{assert_runtime_errors, display, eval};
+    use crate::Lox;
+
+    use super::*;
+
+    #[test]
+    fn test_literals_and_indexing() {
+        let mut lox = Lox::new();
+        lox.run_str("var a = [1, \"two\", [3]]; var b = a;")
+            .unwrap();
+
+        assert_eq!(display(&mut lox, "a"), "[1, \"two\", [3]]");
+        assert_eq!(display(&mut lox, "[]"), "[]");
+        assert_eq!(
+            eval(&mut lox, "a[1]").unwrap(),
+            LoxType::String_("two".into())
+        );
+        assert_eq!(eval(&mut lox, "a[2][0]").unwrap(), LoxType::Number(3.0));
+        assert_eq!(eval(&mut lox, "a[0] = 5").unwrap(), LoxType::Number(5.0));
+        assert_eq!(display(&mut lox, "b"), "[5, \"two\", [3]]");
+        assert_eq!(eval(&mut lox, "a == b").unwrap(), LoxType::Boolean(true));
+        assert_eq!(eval(&mut lox, "[] == []").unwrap(), LoxType::Boolean(false));
+        assert_eq!(
+            eval(&mut lox, "type(a)").unwrap(),
+            LoxType::String_("list".into())
+        );
+        assert_eq!(eval(&mut lox, "len(a)").unwrap(), LoxType::Number(3.0));
+
+        lox.run_str("a.push(a);").unwrap();
+        assert_eq!(display(&mut lox, "a"), "[5, \"two\", [3], [...]]");
+    }
+
+    #[test]
+    fn test_methods() {
+        let mut lox = Lox::new();
+        lox.run_str("var a = [1, 2]; a.push(3); a.insert(0, 0); a.insert(4, 4);")
+            .unwrap();
+
+        assert_eq!(display(&mut lox, "a"), "[0, 1, 2, 3, 4]");
+        assert_eq!(eval(&mut lox, "a.pop()").unwrap(), LoxType::Number(4.0));
+        assert_eq!(eval(&mut lox, "a.remove(1)").unwrap(), LoxType::Number(1.0));
+        assert_eq!(eval(&mut lox, "a.len()").unwrap(), LoxType::Number(3.0));
+        assert_eq!(display(&mut lox, "a.slice(1, 3)"), "[2, 3]");
+        assert_eq!(display(&mut lox, "a.slice(3, 3)"), "[]");
+        assert_eq!(display(&mut lox, "a"), "[0, 2, 3]");
+    }
+
+    #[test]
+    fn test_bounds_errors() {
+        let mut lox = Lox::new();
+        lox.run_str("var a = [1, 2];").unwrap();
+
+        assert_runtime_errors(
+            &mut lox,
+            &[
+                "a[2]",
+                "a[-1]",
+                "a[0.5]",
+                "a[\"0\"]",
+                "a[-1] = 0",
+                "a[2] = 0",
+                "1[0]",
+                "nil[0] = 1",
+                "[].pop()",
+                "a.insert(3, 0)",
+                "a.remove(2)",
+                "a.remove(-1)",
+                "a.slice(2, 1)",
+                "a.slice(0, 3)",
+                "a.push()",
+                "a.missing",
+            ],
+        );
+        assert_eq!(display(&mut lox, "a"), "[1, 2]");
+    }
+}
@@ -1,29 +1,112 @@
-use std::fs::File;
-use std::io::stdout;
-use std::io::{self, BufRead, BufReader, Write};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::process;
+use std::str::FromStr;
+use std::thread;
 
 use structopt::StructOpt;
 
-use crate::ast_interpreter::AstIntepreter;
-use crate::interpreter::LoxInterpreter;
-use crate::parser::parse_tokens;
-use error::LoxResult;
-use lexer::scan_tokens;
-
-mod ast;
-mod ast_interpreter;
-mod error;
-mod interpreter;
-mod lexer;
-mod parser;
-mod token;
-mod types;
-mod stack;
+use lox_rs::error::{LoxError, LoxResult};
+use lox_rs::lexer::{scan_tokens, Lexer};
+use lox_rs::parser::Parser;
+use lox_rs::{printer, repl, Capabilities, Lox, STACK_SIZE};
+
+// Exit statuses, following the reference Lox implementation and sysexits.h
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_SOFTWARE: i32 = 70;
+const EX_IOERR: i32 = 74;
 
 #[derive(StructOpt)]
+#[structopt(name = "lox-rs")]
 struct Cli {
-    #[structopt(help = "Input file")]
-    input: Option<String>,
+    #[structopt(
+        short = "e",
+        long = "eval",
+        help = "Evaluate <code> and exit, printing the value of a lone expression"
+    )]
+    eval: Option<String>,
+    #[structopt(
+        long = "allow-fs",
+        help = "Let scripts read and write files",
+        raw(global = "true")
+    )]
+    allow_fs: bool,
+    #[structopt(
+        long = "allow-env",
+        help = "Let scripts read environment variables",
+        raw(global = "true")
+    )]
+    allow_env: bool,
+    #[structopt(
+        long = "emit",
+        help = "Dump the tokens or AST of <script>, or stdin, like the tokens and ast commands [values: tokens, tokens-json, ast-sexpr, ast-json]"
+    )]
+    emit: Option<Emit>,
+    #[structopt(help = "Script to run, the same as `run <script>`")]
+    script: Option<String>,
+    #[structopt(help = "Arguments for the script, returned by args()")]
+    args: Vec<String>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+/// The dumps selected by `--emit`, from before the `tokens` and `ast` commands.
+enum Emit {
+    Tokens,
+    TokensJson,
+    AstSexpr,
+    AstJson,
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tokens" => Ok(Emit::Tokens),
+            "tokens-json" => Ok(Emit::TokensJson),
+            "ast-sexpr" => Ok(Emit::AstSexpr),
+            "ast-json" => Ok(Emit::AstJson),
+            other => Err(format!("Unknown emit mode '{}'", other)),
+        }
+    }
+}
+
+#[derive(StructOpt)]
+enum Command {
+    #[structopt(name = "run", about = "Run a script")]
+    Run {
+        #[structopt(help = "Script to run, or - for stdin")]
+        file: String,
+        #[structopt(help = "Arguments for the script, returned by args()")]
+        args: Vec<String>,
+    },
+    #[structopt(name = "repl", about = "Start an interactive session (the default)")]
+    Repl,
+    #[structopt(
+        name = "check",
+        about = "Lex and parse scripts without running them, reporting every error"
+    )]
+    Check {
+        #[structopt(help = "Scripts to check, or - for stdin", raw(required = "true"))]
+        files: Vec<String>,
+    },
+    #[structopt(name = "tokens", about = "Print the token stream of the input")]
+    Tokens {
+        #[structopt(long = "json", help = "Print one JSON object per token")]
+        json: bool,
+        #[structopt(help = "Input file, or - for stdin", default_value = "-")]
+        file: String,
+    },
+    #[structopt(name = "ast", about = "Print the parsed AST of the input")]
+    Ast {
+        #[structopt(long = "json", help = "Print JSON instead of s-expressions")]
+        json: bool,
+        #[structopt(help = "Input file, or - for stdin", default_value = "-")]
+        file: String,
+    },
 }
 
 fn open_file(input_file: &str) -> LoxResult<Box<dyn BufRead>> {
@@ -33,16 +116,200 @@ fn open_file(input_file: &str) -> LoxResult<Box<dyn BufRead>> {
     }
 }
 
-fn main() -> LoxResult<()> {
-    let vm = AstIntepreter::default();
-    let args: Cli = Cli::from_args();
-    let input_file = args.input.unwrap_or_else(|| "-".into());
-    let mut file = open_file(&input_file)?;
-    loop {
-        print!("> ");
-        stdout().flush()?;
-        let mut input_str = String::new();
-        file.read_line(&mut input_str)?;
-        println!("{:#?}", vm.eval(input_str.as_str())?);
+fn emit_tokens(mut file: Box<dyn BufRead>, json: bool) -> LoxResult<()> {
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    for token in scan_tokens(&text)? {
+        if json {
+            println!("{}", printer::token_to_json(&token));
+        } else {
+            println!("{}", printer::token_to_text(&token));
+        }
+    }
+    Ok(())
+}
+
+/// Prints the AST of a lone expression, or else of each top-level statement.
+fn emit_ast(mut file: Box<dyn BufRead>, json: bool) -> LoxResult<()> {
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    for line in printer::ast_lines(&scan_tokens(&text)?, json)? {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn exit_code(err: &LoxError) -> i32 {
+    match err {
+        LoxError::InnerLexingError(_) | LoxError::InnerParsingError(_) => EX_DATAERR,
+        LoxError::InnerRuntimeError(_) => EX_SOFTWARE,
+        LoxError::IoError(_) => EX_IOERR,
+        LoxError::Exit(code) => *code,
+    }
+}
+
+/// Prints `err` to stderr, pointing runtime errors at their line in `source`,
+/// and returns the matching exit status.
+fn report(err: &LoxError, source: &str) -> i32 {
+    match err {
+        LoxError::InnerRuntimeError(err) => {
+            eprintln!("{}\n[line {}]", err, err.span.line_in(source))
+        }
+        LoxError::Exit(_) => {}
+        err => eprintln!("{}", err),
+    }
+    exit_code(err)
+}
+
+/// The contents of `path`, or of stdin for `-`.
+fn read_source(path: &str) -> Result<String, i32> {
+    let source = match path {
+        "-" => {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source).map(|_| source)
+        }
+        path => fs::read_to_string(path),
+    };
+    source.map_err(|err| {
+        eprintln!("Can't read {}: {}", path, err);
+        EX_NOINPUT
+    })
+}
+
+/// Runs a whole script, returning the exit status.
+fn run_file(lox: &mut Lox, path: &str) -> i32 {
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(code) => return code,
+    };
+    match lox.run_str(&source) {
+        Ok(()) => 0,
+        Err(err) => report(&err, &source),
+    }
+}
+
+fn eval_code(lox: &mut Lox, code: &str) -> i32 {
+    match repl::eval_entry(lox, code) {
+        Ok(output) => {
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            0
+        }
+        Err(err) => report(&err, code),
+    }
+}
+
+/// Lexes and parses every file, printing all diagnostics found. Returns the
+/// status of the first file that failed.
+fn check_files(files: &[String]) -> i32 {
+    let mut status = 0;
+    for path in files {
+        let source = match read_source(path) {
+            Ok(source) => source,
+            Err(code) => {
+                if status == 0 {
+                    status = code;
+                }
+                continue;
+            }
+        };
+        let (_, errors) = Parser::new(Lexer::new(source.chars())).parse_program_recovering();
+        for err in &errors {
+            eprintln!("{}: {}", path, err);
+        }
+        if !errors.is_empty() && status == 0 {
+            status = EX_DATAERR;
+        }
+    }
+    status
+}
+
+/// Runs one of the `tokens` or `ast` dumps over `path`.
+fn dump(path: &str, json: bool, emit: fn(Box<dyn BufRead>, bool) -> LoxResult<()>) -> i32 {
+    let file = match open_file(path) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Can't read {}: {}", path, err);
+            return EX_NOINPUT;
+        }
+    };
+    match emit(file, json) {
+        Ok(()) => 0,
+        Err(err) => report(&err, ""),
+    }
+}
+
+fn main() {
+    let args = match Cli::clap().get_matches_safe() {
+        Ok(matches) => Cli::from_clap(&matches),
+        // --help and --version aren't failures
+        Err(err) if !err.use_stderr() => err.exit(),
+        Err(err) => {
+            eprintln!("{}", err.message);
+            process::exit(EX_USAGE);
+        }
+    };
+    // Lox calls recurse on the Rust stack, so give them more than the main thread's
+    let status = match thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(args))
+    {
+        // A panic has already been reported by the panic hook
+        Ok(interpreter) => interpreter.join().unwrap_or(EX_SOFTWARE),
+        Err(err) => {
+            eprintln!("Can't start the interpreter: {}", err);
+            EX_SOFTWARE
+        }
+    };
+    process::exit(status);
+}
+
+/// Carries out the command line, returning the exit status.
+fn run(args: Cli) -> i32 {
+    let mut lox = Lox::new();
+    lox.set_capabilities(Capabilities {
+        fs: args.allow_fs,
+        env: args.allow_env,
+    });
+    // The positional script and --emit predate the subcommands and map onto them
+    let command = match (args.command, args.emit, args.script) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+            eprintln!("--emit and a script can't be combined with a subcommand");
+            return EX_USAGE;
+        }
+        (command, None, None) => command,
+        (None, Some(emit), file) => {
+            let file = file.unwrap_or_else(|| "-".into());
+            Some(match emit {
+                Emit::Tokens => Command::Tokens { json: false, file },
+                Emit::TokensJson => Command::Tokens { json: true, file },
+                Emit::AstSexpr => Command::Ast { json: false, file },
+                Emit::AstJson => Command::Ast { json: true, file },
+            })
+        }
+        (None, None, Some(ref file)) if file == "-" => Some(Command::Repl),
+        (None, None, Some(file)) => Some(Command::Run {
+            file,
+            args: args.args,
+        }),
+    };
+    match (args.eval, command) {
+        (Some(_), Some(_)) => {
+            eprintln!("-e can't be combined with a subcommand");
+            EX_USAGE
+        }
+        (Some(code), None) => eval_code(&mut lox, &code),
+        (None, Some(Command::Run { file, args })) => {
+            lox.set_args(args);
+            run_file(&mut lox, &file)
+        }
+        (None, Some(Command::Check { files })) => check_files(&files),
+        (None, Some(Command::Tokens { json, file })) => dump(&file, json, emit_tokens),
+        (None, Some(Command::Ast { json, file })) => dump(&file, json, emit_ast),
+        (None, Some(Command::Repl)) | (None, None) => match repl::run(&mut lox) {
+            Ok(()) => 0,
+            Err(err) => report(&err, ""),
+        },
     }
 }
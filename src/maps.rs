@@ -0,0 +1,163 @@
+//! Methods available on map values, e.g. `map.keys()`.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::error::LoxResult;
+use crate::native::{bound_method, Context, Method};
+use crate::types::{LoxMap, LoxType, MapKey};
+
+type Map = Rc<RefCell<LoxMap>>;
+
+/// Looks up `name` on map `receiver`, bound to it as a function value.
+pub fn method(receiver: &Map, name: &str) -> Option<LoxType> {
+    let (arity, method): (usize, Method<Map>) = match name {
+        "keys" => (0, |_, map, _| {
+            Ok(LoxType::list(
+                map.borrow().iter().map(|(key, _)| key.to_value()).collect(),
+            ))
+        }),
+        "values" => (0, |_, map, _| {
+            Ok(LoxType::list(
+                map.borrow()
+                    .iter()
+                    .map(|(_, value)| value.clone())
+                    .collect(),
+            ))
+        }),
+        "len" => (0, |_, map, _| {
+            Ok(LoxType::Number(map.borrow().len() as f64))
+        }),
+        "has" => (1, has),
+        "remove" => (1, remove),
+        _ => return None,
+    };
+    Some(bound_method(receiver.clone(), name, arity, method))
+}
+
+fn key_arg(ctx: &Context, arg: &LoxType) -> LoxResult<MapKey> {
+    MapKey::new(arg).map_err(|message| ctx.error(message))
+}
+
+fn has(ctx: &mut Context, map: &Map, args: &[LoxType]) -> LoxResult<LoxType> {
+    let key = key_arg(ctx, &args[0])?;
+    Ok(LoxType::Boolean(map.borrow().contains_key(&key)))
+}
+
+/// Removes the argument's entry, returning its value.
+fn remove(ctx: &mut Context, map: &Map, args: &[LoxType]) -> LoxResult<LoxType> {
+    let key = key_arg(ctx, &args[0])?;
+    let removed = map.borrow_mut().remove(&key);
+    removed.ok_or_else(|| ctx.error(format!("remove() key {} isn't in the map.", key)))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::{assert_runtime_errors, display, eval};
+    use crate::Lox;
+
+    use super::*;
+
+    #[test]
+    fn test_literals_and_indexing() {
+        let mut lox = Lox::new();
+        lox.run_str("var m = {\"b\": 1, 2: [\"x\"], \"a\": {}}; var n = m;")
+            .unwrap();
+
+        assert_eq!(display(&mut lox, "m"), r#"{"b": 1, 2: ["x"], "a": {}}"#);
+        assert_eq!(display(&mut lox, "{}"), "{}");
+        assert_eq!(eval(&mut lox, "m[\"b\"]").unwrap(), LoxType::Number(1.0));
+        assert_eq!(display(&mut lox, "m[2]"), r#"["x"]"#);
+        assert_eq!(
+            eval(&mut lox, "m[\"b\"] = 3").unwrap(),
+            LoxType::Number(3.0)
+        );
+        lox.run_str("m[\"c\"] = nil; m[\"a\"][0] = true;").unwrap();
+        assert_eq!(
+            display(&mut lox, "n"),
+            r#"{"b": 3, 2: ["x"], "a": {0: true}, "c": nil}"#
+        );
+        assert_eq!(eval(&mut lox, "m == n").unwrap(), LoxType::Boolean(true));
+        assert_eq!(eval(&mut lox, "{} == {}").unwrap(), LoxType::Boolean(false));
+        assert_eq!(
+            eval(&mut lox, "type(m)").unwrap(),
+            LoxType::String_("map".into())
+        );
+        assert_eq!(eval(&mut lox, "len(m)").unwrap(), LoxType::Number(4.0));
+
+        lox.run_str("m[\"self\"] = m;").unwrap();
+        assert!(display(&mut lox, "m").ends_with(r#""self": {...}}"#));
+    }
+
+    #[test]
+    fn test_key_equality() {
+        let mut lox = Lox::new();
+        lox.run_str("var m = {1: \"one\", \"1\": \"string\"}; m[-0] = \"zero\";")
+            .unwrap();
+
+        assert_eq!(
+            eval(&mut lox, "m[1.0]").unwrap(),
+            LoxType::String_("one".into())
+        );
+        assert_eq!(
+            eval(&mut lox, "m[\"1\"]").unwrap(),
+            LoxType::String_("string".into())
+        );
+        assert_eq!(
+            eval(&mut lox, "m[0]").unwrap(),
+            LoxType::String_("zero".into())
+        );
+        assert_eq!(
+            eval(&mut lox, "m.has(\"a\" + \"\")").unwrap(),
+            LoxType::Boolean(false)
+        );
+        assert_eq!(
+            eval(&mut lox, "m.has(2 - 1)").unwrap(),
+            LoxType::Boolean(true)
+        );
+        assert_eq!(display(&mut lox, "m.keys()"), r#"[1, "1", 0]"#);
+    }
+
+    #[test]
+    fn test_methods_keep_insertion_order() {
+        let mut lox = Lox::new();
+        lox.run_str("var m = {\"z\": 1, \"a\": 2, \"m\": 3}; m[\"z\"] = 4; m[\"b\"] = 5;")
+            .unwrap();
+
+        assert_eq!(display(&mut lox, "m.keys()"), r#"["z", "a", "m", "b"]"#);
+        assert_eq!(display(&mut lox, "m.values()"), "[4, 2, 3, 5]");
+        assert_eq!(
+            eval(&mut lox, "m.remove(\"a\")").unwrap(),
+            LoxType::Number(2.0)
+        );
+        assert_eq!(eval(&mut lox, "m.len()").unwrap(), LoxType::Number(3.0));
+        lox.run_str("m[\"a\"] = 6;").unwrap();
+        assert_eq!(
+            display(&mut lox, "m"),
+            r#"{"z": 4, "m": 3, "b": 5, "a": 6}"#
+        );
+        assert_eq!(eval(&mut lox, "m[\"b\"]").unwrap(), LoxType::Number(5.0));
+    }
+
+    #[test]
+    fn test_bad_keys() {
+        let mut lox = Lox::new();
+        lox.run_str("var m = {\"a\": 1};").unwrap();
+
+        assert_runtime_errors(
+            &mut lox,
+            &[
+                "m[\"missing\"]",
+                "m[nil]",
+                "m[true] = 1",
+                "m[[]] = 1",
+                "m[0/0] = 1",
+                "{nil: 1}",
+                "m.has(m)",
+                "m.remove(\"missing\")",
+                "m.missing",
+            ],
+        );
+        assert_eq!(display(&mut lox, "m"), r#"{"a": 1}"#);
+    }
+}
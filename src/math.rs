@@ -0,0 +1,171 @@
+use std::f64::consts;
+
+use crate::error::LoxResult;
+use crate::native::{wrong_type, Arity, Context};
+use crate::prelude::define;
+use crate::stack::Frame;
+use crate::types::LoxType;
+
+/// Binds the math builtins and the `PI` and `E` constants into `globals`.
+pub fn install(globals: &Frame) {
+    globals.define("PI", LoxType::Number(consts::PI));
+    globals.define("E", LoxType::Number(consts::E));
+
+    unary(globals, "floor", f64::floor);
+    unary(globals, "ceil", f64::ceil);
+    unary(globals, "round", f64::round);
+    unary(globals, "sqrt", f64::sqrt);
+    unary(globals, "abs", f64::abs);
+    unary(globals, "sin", f64::sin);
+    unary(globals, "cos", f64::cos);
+    unary(globals, "tan", f64::tan);
+    unary(globals, "log", f64::ln);
+    unary(globals, "exp", f64::exp);
+    define(globals, "pow", Arity::Fixed(2), |ctx, args| {
+        let base = number_arg(ctx, "pow", &args[0])?;
+        let exponent = number_arg(ctx, "pow", &args[1])?;
+        Ok(LoxType::Number(base.powf(exponent)))
+    });
+    define(globals, "min", Arity::Variadic, |ctx, args| {
+        fold(ctx, "min", args, f64::min)
+    });
+    define(globals, "max", Arity::Variadic, |ctx, args| {
+        fold(ctx, "max", args, f64::max)
+    });
+    define(globals, "random", Arity::Fixed(0), |ctx, _| {
+        Ok(LoxType::Number(ctx.random()))
+    });
+    define(globals, "seed", Arity::Fixed(1), |ctx, args| {
+        match args[0] {
+            LoxType::Number(seed) if seed.fract() == 0.0 && seed >= 0.0 => {
+                ctx.seed_random(seed as u64);
+                Ok(LoxType::Nil)
+            }
+            _ => Err(ctx.error("seed() takes a non-negative integer.")),
+        }
+    });
+}
+
+fn unary(globals: &Frame, name: &'static str, function: fn(f64) -> f64) {
+    define(globals, name, Arity::Fixed(1), move |ctx, args| {
+        Ok(LoxType::Number(function(number_arg(ctx, name, &args[0])?)))
+    });
+}
+
+fn number_arg(ctx: &Context, name: &str, arg: &LoxType) -> LoxResult<f64> {
+    match arg {
+        LoxType::Number(num) => Ok(*num),
+        other => Err(wrong_type(ctx, name, "numbers", other)),
+    }
+}
+
+/// Combines one or more number arguments pairwise.
+fn fold(
+    ctx: &Context,
+    name: &str,
+    args: &[LoxType],
+    combine: fn(f64, f64) -> f64,
+) -> LoxResult<LoxType> {
+    let (first, rest) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(ctx.error(format!("{}() takes at least one number.", name))),
+    };
+    let mut result = number_arg(ctx, name, first)?;
+    for arg in rest {
+        result = combine(result, number_arg(ctx, name, arg)?);
+    }
+    Ok(LoxType::Number(result))
+}
+
+/// Xorshift64* generator behind `random()`. Not suitable for cryptography.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Run the seed through SplitMix64 so small seeds still give good
+        // output, and so the state is never zero
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Rng {
+            state: (z ^ (z >> 31)) | 1,
+        }
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let bits = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::{assert_runtime_errors, eval};
+    use crate::Lox;
+
+    use super::*;
+
+    fn number(lox: &mut Lox, source: &str) -> f64 {
+        match eval(lox, source).unwrap() {
+            LoxType::Number(num) => num,
+            value => panic!("Expected a number from {:?}, got {:?}", source, value),
+        }
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut lox = Lox::new();
+
+        for (source, expected) in &[
+            ("floor(-1.5)", -2.0),
+            ("ceil(1.2)", 2.0),
+            ("round(2.5)", 3.0),
+            ("round(-2.5)", -3.0),
+            ("sqrt(16)", 4.0),
+            ("pow(2, 10)", 1024.0),
+            ("abs(-3)", 3.0),
+            ("min(3, 1, 2)", 1.0),
+            ("max(3)", 3.0),
+            ("sin(0)", 0.0),
+            ("cos(0)", 1.0),
+            ("tan(0)", 0.0),
+            ("log(E)", 1.0),
+            ("exp(0)", 1.0),
+            ("PI", consts::PI),
+        ] {
+            assert_eq!(number(&mut lox, source), *expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_bad_arguments() {
+        let mut lox = Lox::new();
+
+        assert_runtime_errors(
+            &mut lox,
+            &["sqrt(\"4\")", "min()", "max(1, nil)", "pow(2)", "seed(-1)"],
+        );
+    }
+
+    #[test]
+    fn test_random_is_reproducible() {
+        let mut first = Lox::new();
+        let mut second = Lox::new();
+        first.seed_random(42);
+        second.run_str("seed(42);").unwrap();
+
+        let draws: Vec<f64> = (0..100).map(|_| number(&mut first, "random()")).collect();
+        for draw in &draws {
+            assert!((0.0..1.0).contains(draw));
+            assert_eq!(number(&mut second, "random()"), *draw);
+        }
+        first.seed_random(43);
+        assert_ne!(number(&mut first, "random()"), draws[0]);
+    }
+}
@@ -0,0 +1,226 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::error::{LoxError, LoxResult, RuntimeError};
+use crate::system::Capabilities;
+use crate::token::Span;
+use crate::types::LoxType;
+
+/// How many arguments a native function accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Fixed(usize),
+    Variadic,
+}
+
+pub type NativeFn = dyn Fn(&mut Context, &[LoxType]) -> LoxResult<LoxType>;
+
+/// A function implemented in Rust and callable from Lox.
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: Arity,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: impl Into<String>,
+        arity: Arity,
+        function: impl Fn(&mut Context, &[LoxType]) -> LoxResult<LoxType> + 'static,
+    ) -> NativeFunction {
+        NativeFunction {
+            name: name.into(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    pub(crate) fn call(&self, context: &mut Context, args: &[LoxType]) -> LoxResult<LoxType> {
+        (self.function)(context, args)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// A host object scripts can use like an instance of a Lox class, through
+/// `.` property access, assignment and method calls. Methods take `&self`,
+/// since scripts share the object, so mutable state needs interior mutability.
+pub trait NativeObject {
+    /// Shown when an instance is printed, as `<class_name> instance`.
+    fn class_name(&self) -> &str;
+
+    /// The value of field `name`, or `None` if there is no such field.
+    fn get_property(&self, _name: &str) -> Option<LoxType> {
+        None
+    }
+
+    fn set_property(&self, context: &mut Context, name: &str, _value: LoxType) -> LoxResult<()> {
+        Err(context.error(format!(
+            "Can't set property '{}' on {} instance.",
+            name,
+            self.class_name()
+        )))
+    }
+
+    /// Whether `call_method` accepts `name`.
+    fn has_method(&self, _name: &str) -> bool {
+        false
+    }
+
+    fn call_method(
+        &self,
+        context: &mut Context,
+        name: &str,
+        _args: &[LoxType],
+    ) -> LoxResult<LoxType> {
+        Err(context.error(format!("Undefined property '{}'.", name)))
+    }
+}
+
+impl fmt::Debug for dyn NativeObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{} instance>", self.class_name())
+    }
+}
+
+/// What the interpreter exposes to native functions while they run.
+pub(crate) trait Host {
+    fn call(&self, callee: &LoxType, args: &[LoxType], span: Span) -> LoxResult<LoxType>;
+    fn get_global(&self, name: &str) -> Option<LoxType>;
+    fn set_global(&self, name: &str, value: LoxType);
+    fn read_line(&self) -> LoxResult<Option<String>>;
+    fn random(&self) -> f64;
+    fn seed_random(&self, seed: u64);
+    fn capabilities(&self) -> Capabilities;
+    fn args(&self) -> Vec<String>;
+}
+
+/// Handle a native function gets on the interpreter calling it.
+pub struct Context<'c> {
+    host: &'c dyn Host,
+    span: Span,
+}
+
+impl<'c> Context<'c> {
+    pub(crate) fn new(host: &'c dyn Host, span: Span) -> Context<'c> {
+        Context { host, span }
+    }
+
+    /// Calls a Lox or native function, e.g. one passed in as a callback.
+    pub fn call(&mut self, callee: &LoxType, args: &[LoxType]) -> LoxResult<LoxType> {
+        self.host.call(callee, args, self.span)
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<LoxType> {
+        self.host.get_global(name)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: LoxType) {
+        self.host.set_global(name, value);
+    }
+
+    /// Reads a line from the interpreter's input without its line ending, or
+    /// `None` at the end of input.
+    pub fn read_line(&mut self) -> LoxResult<Option<String>> {
+        self.host.read_line()
+    }
+
+    /// The next number in `[0, 1)` from the interpreter's random generator.
+    pub fn random(&mut self) -> f64 {
+        self.host.random()
+    }
+
+    pub fn seed_random(&mut self, seed: u64) {
+        self.host.seed_random(seed);
+    }
+
+    /// What the host allows scripts to access.
+    pub fn capabilities(&self) -> Capabilities {
+        self.host.capabilities()
+    }
+
+    /// Arguments the host passed to the script.
+    pub fn args(&self) -> Vec<String> {
+        self.host.args()
+    }
+
+    /// A runtime error pointing at the call being made.
+    pub fn error(&self, message: impl Into<String>) -> LoxError {
+        RuntimeError::new(message, self.span).into()
+    }
+}
+
+/// A method on a built-in type, taking its receiver first.
+pub(crate) type Method<T> = fn(&mut Context, &T, &[LoxType]) -> LoxResult<LoxType>;
+
+/// `method` bound to `receiver` as a function value, e.g. for `list.push`.
+pub(crate) fn bound_method<R, T>(
+    receiver: R,
+    name: &str,
+    arity: usize,
+    method: Method<T>,
+) -> LoxType
+where
+    R: Borrow<T> + 'static,
+    T: ?Sized + 'static,
+{
+    let bound = NativeFunction::new(name, Arity::Fixed(arity), move |ctx, args| {
+        method(ctx, receiver.borrow(), args)
+    });
+    LoxType::NativeFunction(Rc::new(bound))
+}
+
+/// A runtime error for a builtin given an argument of the wrong type.
+pub(crate) fn wrong_type(ctx: &Context, name: &str, expected: &str, got: &LoxType) -> LoxError {
+    ctx.error(format!(
+        "{}() takes {} but got {}.",
+        name,
+        expected,
+        got.type_name()
+    ))
+}
+
+pub(crate) fn string_arg<'v>(ctx: &Context, name: &str, arg: &'v LoxType) -> LoxResult<&'v str> {
+    match arg {
+        LoxType::String_(s) => Ok(s),
+        other => Err(wrong_type(ctx, name, "a string", other)),
+    }
+}
+
+/// Checks `index` against a sequence of `len` elements, allowing it to point
+/// one past the end if `allow_end`. Errors describe the index, e.g. "index 3
+/// is out of bounds for length 2.", for the caller to prefix.
+pub(crate) fn check_index(index: &LoxType, len: usize, allow_end: bool) -> Result<usize, String> {
+    let max = if allow_end { len + 1 } else { len };
+    match index {
+        LoxType::Number(idx) if idx.fract() != 0.0 || !idx.is_finite() => {
+            Err(format!("index must be an integer but got {}.", idx))
+        }
+        LoxType::Number(idx) if *idx < 0.0 => Err(format!("index {} is negative.", idx)),
+        LoxType::Number(idx) if *idx >= max as f64 => Err(format!(
+            "index {} is out of bounds for length {}.",
+            idx, len
+        )),
+        LoxType::Number(idx) => Ok(*idx as usize),
+        other => Err(format!(
+            "index must be an integer but got {}.",
+            other.type_name()
+        )),
+    }
+}
+
+/// An index argument to method `name`, checked as by `check_index`.
+pub(crate) fn index_arg(
+    ctx: &Context,
+    name: &str,
+    arg: &LoxType,
+    len: usize,
+    allow_end: bool,
+) -> LoxResult<usize> {
+    check_index(arg, len, allow_end).map_err(|message| ctx.error(format!("{}() {}", name, message)))
+}
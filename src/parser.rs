@@ -1,85 +1,807 @@
-use crate::ast::{BinaryOp, Expr, UnaryOp};
-use crate::error::{LoxResult, ParsingError};
-use crate::token::{Token, TokenType};
+use std::iter::Peekable;
+use std::mem;
+use std::rc::Rc;
+
+use crate::ast::{BinaryOp, Expr, ExprKind, FunctionDecl, LogicalOp, Stmt, StmtKind, UnaryOp};
+use crate::error::{LoxError, LoxResult, ParsingError};
+use crate::token::{Span, Token, TokenType};
 use crate::types::LoxType;
 
 macro_rules! binary_rule {
-            ($rule:ident, $next_priority:ident, $( $token_type:ident )*) => {
-                fn $rule(tokens: &[Token]) -> LoxResult<(Expr, &[Token])> {
-                    let (expr, tail) = $next_priority(tokens)?;
-                    match &tail.first() {
-                        $(
-                            Some(Token { token_type: TokenType::$token_type, .. }) => {
-                                let (inner_expr, inner_tail) = equality(&tail[1..])?;
-                                Ok((Expr::Binary(
-                                    Box::new(expr),
-                                    BinaryOp::$token_type,
-                                    Box::new(inner_expr),
-                                ), inner_tail))
-                            }
-                        )*
-                        _ => { Ok((expr, tail)) }
+            ($rule:ident, $next_priority:ident, $kind:ident, $op:ident, $( $token_type:ident )*) => {
+                fn $rule(&mut self) -> LoxResult<Expr> {
+                    let mut expr = self.$next_priority()?;
+                    loop {
+                        let op = match self.peek()?.map(|token| &token.token_type) {
+                            $(
+                                Some(TokenType::$token_type) => $op::$token_type,
+                            )*
+                            _ => return Ok(expr),
+                        };
+                        self.advance()?;
+                        let right = self.$next_priority()?;
+                        let span = expr.span.to(right.span);
+                        expr = Expr::new(
+                            ExprKind::$kind(Box::new(expr), op, Box::new(right)),
+                            span,
+                        );
                     }
                 }
             }
         }
 
+/// How deeply expressions and statements may nest, so that parsing, and
+/// evaluating what was parsed, can't overflow the stack.
+const MAX_NESTING: usize = 128;
+
+/// Parses a single expression spanning all of `tokens`.
 pub fn parse_tokens(tokens: &[Token]) -> LoxResult<Expr> {
-    expression(tokens).map(|(expr, _)| expr)
+    Parser::new(tokens.iter().cloned().map(Ok)).parse()
+}
+
+/// Parses a whole program: a sequence of declarations and statements.
+pub fn parse_program(tokens: &[Token]) -> LoxResult<Vec<Stmt>> {
+    Parser::new(tokens.iter().cloned().map(Ok)).parse_program()
 }
 
-fn expression(tokens: &[Token]) -> LoxResult<(Expr, &[Token])> {
-    equality(tokens)
+/// Recursive descent parser pulling tokens on demand from any token stream,
+/// such as a `Lexer`.
+pub struct Parser<I: Iterator<Item = LoxResult<Token>>> {
+    tokens: Peekable<I>,
+    /// How many function bodies enclose the current position.
+    function_depth: usize,
+    /// How many loop bodies enclose the current position, within the
+    /// innermost function.
+    loop_depth: usize,
+    /// How many nested expressions and statements enclose the current
+    /// position.
+    nesting: usize,
 }
 
-binary_rule!(equality, comparison, EqualEqual BangEqual);
-binary_rule!(comparison, addition, Greater GreaterEqual Less LessEqual);
-binary_rule!(addition, multiplication, Plus Minus);
-binary_rule!(multiplication, unary, Slash Star);
+impl<I: Iterator<Item = LoxResult<Token>>> Parser<I> {
+    pub fn new(tokens: I) -> Self {
+        Parser {
+            tokens: tokens.peekable(),
+            function_depth: 0,
+            loop_depth: 0,
+            nesting: 0,
+        }
+    }
+
+    /// Parses a single expression, which must make up the rest of the input.
+    pub fn parse(&mut self) -> LoxResult<Expr> {
+        let expr = self.expression()?;
+        match self.peek()? {
+            Some(token) if token.token_type != TokenType::Eof => {
+                Err(ParsingError::UnexpectedToken(token.clone()))?
+            }
+            _ => Ok(expr),
+        }
+    }
+
+    pub fn parse_program(&mut self) -> LoxResult<Vec<Stmt>> {
+        let mut statements = Vec::new();
+        while !self.at_end()? {
+            statements.push(self.declaration()?);
+        }
+        Ok(statements)
+    }
+
+    /// Parses a whole program, skipping to the next statement after each error
+    /// so that every diagnostic is reported rather than just the first.
+    pub fn parse_program_recovering(&mut self) -> (Vec<Stmt>, Vec<LoxError>) {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            match self.at_end() {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            }
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(err) => {
+                    errors.push(err);
+                    self.synchronize(&mut errors);
+                }
+            }
+        }
+        (statements, errors)
+    }
+
+    /// Discards tokens up to the end of the current statement.
+    fn synchronize(&mut self, errors: &mut Vec<LoxError>) {
+        loop {
+            let token_type = match self.peek() {
+                Ok(Some(token)) => &token.token_type,
+                Ok(None) => return,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            };
+            match token_type {
+                TokenType::Eof
+                | TokenType::Class
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                TokenType::Semicolon => {
+                    self.tokens.next();
+                    return;
+                }
+                _ => {
+                    self.tokens.next();
+                }
+            }
+        }
+    }
 
-fn unary(tokens: &[Token]) -> LoxResult<(Expr, &[Token])> {
-    let token = tokens.first().expect("First token didn't exist");
-    match &token.token_type {
-        TokenType::Bang => {
-            let (expr, tail) = unary(&tokens[1..])?;
-            Ok((Expr::Unary(UnaryOp::Bang, Box::new(expr)), tail))
+    /// Returns the next token without consuming it, surfacing lexing errors.
+    fn peek(&mut self) -> LoxResult<Option<&Token>> {
+        if let Some(Err(_)) = self.tokens.peek() {
+            return Err(self.tokens.next().unwrap().unwrap_err());
         }
-        TokenType::Minus => {
-            let (expr, tail) = unary(&tokens[1..])?;
-            Ok((Expr::Unary(UnaryOp::Minus, Box::new(expr)), tail))
+        Ok(self.tokens.peek().map(|token| token.as_ref().unwrap()))
+    }
+
+    fn advance(&mut self) -> LoxResult<Token> {
+        match self.tokens.next() {
+            Some(token) => token,
+            None => Err(ParsingError::UnexpectedEof)?,
+        }
+    }
+
+    fn check(&mut self, token_type: &TokenType) -> LoxResult<bool> {
+        Ok(self
+            .peek()?
+            .is_some_and(|token| &token.token_type == token_type))
+    }
+
+    fn at_end(&mut self) -> LoxResult<bool> {
+        Ok(self
+            .peek()?
+            .is_none_or(|token| token.token_type == TokenType::Eof))
+    }
+
+    /// Consumes the next token if it is of type `token_type`.
+    fn advance_if(&mut self, token_type: &TokenType) -> LoxResult<Option<Token>> {
+        if self.check(token_type)? {
+            self.advance().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Consumes the next token, failing unless it is of type `expected`.
+    fn consume(&mut self, expected: TokenType) -> LoxResult<Token> {
+        match self.peek()? {
+            Some(token) if token.token_type == expected => self.advance(),
+            Some(token) => Err(ParsingError::ExpectedToken(expected, token.clone()))?,
+            None => Err(ParsingError::UnexpectedEof)?,
+        }
+    }
+
+    fn consume_identifier(&mut self) -> LoxResult<String> {
+        match self.peek()? {
+            Some(Token {
+                token_type: TokenType::Identifier(name),
+                ..
+            }) => {
+                let name = name.clone();
+                self.advance()?;
+                Ok(name)
+            }
+            Some(token) => Err(ParsingError::ExpectedToken(
+                TokenType::Identifier(String::new()),
+                token.clone(),
+            ))?,
+            None => Err(ParsingError::UnexpectedEof)?,
+        }
+    }
+
+    /// Parses with `rule` one level further in, failing past `MAX_NESTING`.
+    fn nested<T>(&mut self, rule: impl FnOnce(&mut Self) -> LoxResult<T>) -> LoxResult<T> {
+        if self.nesting >= MAX_NESTING {
+            let token = self.advance()?;
+            Err(ParsingError::TooDeep(token))?
+        }
+        self.nesting += 1;
+        let result = rule(self);
+        self.nesting -= 1;
+        result
+    }
+
+    fn declaration(&mut self) -> LoxResult<Stmt> {
+        self.nested(|parser| {
+            if let Some(var) = parser.advance_if(&TokenType::Var)? {
+                parser.var_declaration(var)
+            } else if let Some(fun) = parser.advance_if(&TokenType::Fun)? {
+                parser.function(fun)
+            } else {
+                parser.statement()
+            }
+        })
+    }
+
+    fn function(&mut self, fun: Token) -> LoxResult<Stmt> {
+        let name = self.consume_identifier()?;
+        self.consume(TokenType::LeftParen)?;
+        let mut params = Vec::new();
+        if !self.check(&TokenType::RightParen)? {
+            loop {
+                params.push(self.consume_identifier()?);
+                if self.advance_if(&TokenType::Comma)?.is_none() {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen)?;
+        self.consume(TokenType::LeftBrace)?;
+        // A loop around the declaration doesn't let its body break out
+        let loop_depth = mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        let body = self.block();
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        let (body, closing) = body?;
+        Ok(Stmt::new(
+            StmtKind::Function(Rc::new(FunctionDecl { name, params, body })),
+            fun.span.to(closing),
+        ))
+    }
+
+    fn var_declaration(&mut self, var: Token) -> LoxResult<Stmt> {
+        let name = self.consume_identifier()?;
+        let initializer = match self.advance_if(&TokenType::Equal)? {
+            Some(_) => Some(self.expression()?),
+            None => None,
+        };
+        let semicolon = self.consume(TokenType::Semicolon)?;
+        Ok(Stmt::new(
+            StmtKind::Var(name, initializer),
+            var.span.to(semicolon.span),
+        ))
+    }
+
+    fn statement(&mut self) -> LoxResult<Stmt> {
+        match self.peek()?.map(|token| &token.token_type) {
+            Some(TokenType::Print) => {
+                let print = self.advance()?;
+                let expr = self.expression()?;
+                let semicolon = self.consume(TokenType::Semicolon)?;
+                Ok(Stmt::new(
+                    StmtKind::Print(expr),
+                    print.span.to(semicolon.span),
+                ))
+            }
+            // A statement starting with `{` is always a block, so map
+            // literals only appear where an expression is expected
+            Some(TokenType::LeftBrace) => {
+                let opening = self.advance()?;
+                let (statements, closing) = self.block()?;
+                Ok(Stmt::new(
+                    StmtKind::Block(statements),
+                    opening.span.to(closing),
+                ))
+            }
+            Some(TokenType::If) => {
+                let token = self.advance()?;
+                self.if_statement(token)
+            }
+            Some(TokenType::While) => {
+                let token = self.advance()?;
+                self.consume(TokenType::LeftParen)?;
+                let condition = self.expression()?;
+                self.consume(TokenType::RightParen)?;
+                let body = self.loop_body()?;
+                let span = token.span.to(body.span);
+                Ok(Stmt::new(
+                    StmtKind::While(condition, Box::new(body), None),
+                    span,
+                ))
+            }
+            Some(TokenType::For) => {
+                let token = self.advance()?;
+                self.for_statement(token)
+            }
+            Some(TokenType::Return) => {
+                let token = self.advance()?;
+                if self.function_depth == 0 {
+                    Err(ParsingError::ReturnOutsideFunction(token.clone()))?
+                }
+                let value = if self.check(&TokenType::Semicolon)? {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                let semicolon = self.consume(TokenType::Semicolon)?;
+                Ok(Stmt::new(
+                    StmtKind::Return(value),
+                    token.span.to(semicolon.span),
+                ))
+            }
+            Some(TokenType::Break) | Some(TokenType::Continue) => {
+                let token = self.advance()?;
+                if self.loop_depth == 0 {
+                    Err(ParsingError::OutsideLoop(token.clone()))?
+                }
+                let semicolon = self.consume(TokenType::Semicolon)?;
+                let kind = if token.token_type == TokenType::Break {
+                    StmtKind::Break
+                } else {
+                    StmtKind::Continue
+                };
+                Ok(Stmt::new(kind, token.span.to(semicolon.span)))
+            }
+            _ => self.expression_statement(),
+        }
+    }
+
+    fn expression_statement(&mut self) -> LoxResult<Stmt> {
+        let expr = self.expression()?;
+        let semicolon = self.consume(TokenType::Semicolon)?;
+        let span = expr.span.to(semicolon.span);
+        Ok(Stmt::new(StmtKind::Expression(expr), span))
+    }
+
+    /// Parses the rest of a block after its opening brace, also giving the
+    /// span of its closing brace.
+    fn block(&mut self) -> LoxResult<(Vec<Stmt>, Span)> {
+        let mut statements = Vec::new();
+        while !self.check(&TokenType::RightBrace)? && !self.at_end()? {
+            statements.push(self.declaration()?);
+        }
+        let closing = self.consume(TokenType::RightBrace)?;
+        Ok((statements, closing.span))
+    }
+
+    fn if_statement(&mut self, if_token: Token) -> LoxResult<Stmt> {
+        self.consume(TokenType::LeftParen)?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen)?;
+        let then_branch = Box::new(self.nested(Self::statement)?);
+        let else_branch = match self.advance_if(&TokenType::Else)? {
+            Some(_) => Some(Box::new(self.nested(Self::statement)?)),
+            None => None,
+        };
+        let last = else_branch.as_ref().unwrap_or(&then_branch);
+        let span = if_token.span.to(last.span);
+        Ok(Stmt::new(
+            StmtKind::If(condition, then_branch, else_branch),
+            span,
+        ))
+    }
+
+    /// Desugars `for (init; condition; increment) body` into a `while` loop.
+    fn for_statement(&mut self, for_token: Token) -> LoxResult<Stmt> {
+        self.consume(TokenType::LeftParen)?;
+        let initializer = if self.advance_if(&TokenType::Semicolon)?.is_some() {
+            None
+        } else if let Some(var) = self.advance_if(&TokenType::Var)? {
+            Some(self.var_declaration(var)?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+        let condition = if self.check(&TokenType::Semicolon)? {
+            Expr::new(ExprKind::Literal(LoxType::Boolean(true)), for_token.span)
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon)?;
+        let increment = if self.check(&TokenType::RightParen)? {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen)?;
+
+        let body = self.loop_body()?;
+        let span = for_token.span.to(body.span);
+        let mut desugared = Stmt::new(StmtKind::While(condition, Box::new(body), increment), span);
+        if let Some(initializer) = initializer {
+            desugared = Stmt::new(StmtKind::Block(vec![initializer, desugared]), span);
+        }
+        Ok(desugared)
+    }
+
+    fn loop_body(&mut self) -> LoxResult<Stmt> {
+        self.loop_depth += 1;
+        let body = self.nested(Self::statement);
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn expression(&mut self) -> LoxResult<Expr> {
+        self.comma()
+    }
+
+    // Lowest precedence, so arguments and elements are parsed with
+    // `assignment` to keep their commas as separators
+    binary_rule!(comma, assignment, Binary, BinaryOp, Comma);
+
+    fn assignment(&mut self) -> LoxResult<Expr> {
+        self.nested(|parser| {
+            let expr = parser.conditional()?;
+            if let Some(equals) = parser.advance_if(&TokenType::Equal)? {
+                let value = parser.assignment()?;
+                let span = expr.span.to(value.span);
+                return match expr.kind {
+                    ExprKind::Variable(name) => {
+                        Ok(Expr::new(ExprKind::Assign(name, Box::new(value)), span))
+                    }
+                    ExprKind::Get(object, name) => Ok(Expr::new(
+                        ExprKind::Set(object, name, Box::new(value)),
+                        span,
+                    )),
+                    ExprKind::Index(object, index) => Ok(Expr::new(
+                        ExprKind::SetIndex(object, index, Box::new(value)),
+                        span,
+                    )),
+                    _ => Err(ParsingError::InvalidAssignmentTarget(equals))?,
+                };
+            }
+            Ok(expr)
+        })
+    }
+
+    /// `condition ? then : otherwise`, where `otherwise` may be another
+    /// conditional, so they group to the right.
+    fn conditional(&mut self) -> LoxResult<Expr> {
+        let condition = self.logic_or()?;
+        let question = match self.advance_if(&TokenType::Question)? {
+            Some(question) => question,
+            None => return Ok(condition),
+        };
+        let then = self.assignment()?;
+        if self.advance_if(&TokenType::Colon)?.is_none() {
+            let found = self.advance()?;
+            Err(ParsingError::MissingColon {
+                question_line: question.line,
+                found,
+            })?
+        }
+        let otherwise = self.assignment()?;
+        let span = condition.span.to(otherwise.span);
+        Ok(Expr::new(
+            ExprKind::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)),
+            span,
+        ))
+    }
+
+    binary_rule!(logic_or, logic_and, Logical, LogicalOp, Or);
+    binary_rule!(logic_and, equality, Logical, LogicalOp, And);
+    binary_rule!(equality, comparison, Binary, BinaryOp, EqualEqual BangEqual);
+    binary_rule!(comparison, bit_or, Binary, BinaryOp, Greater GreaterEqual Less LessEqual);
+    // Bitwise operators bind tighter than comparisons, unlike in C, so
+    // `a & mask == 0` means `(a & mask) == 0`
+    binary_rule!(bit_or, bit_xor, Binary, BinaryOp, Pipe);
+    binary_rule!(bit_xor, bit_and, Binary, BinaryOp, Caret);
+    binary_rule!(bit_and, shift, Binary, BinaryOp, Ampersand);
+    binary_rule!(shift, addition, Binary, BinaryOp, LessLess GreaterGreater);
+    binary_rule!(addition, multiplication, Binary, BinaryOp, Plus Minus);
+    binary_rule!(multiplication, unary, Binary, BinaryOp, Slash Star Percent TildeSlash);
+
+    fn unary(&mut self) -> LoxResult<Expr> {
+        let op = match self.peek()?.map(|token| &token.token_type) {
+            Some(TokenType::Bang) => UnaryOp::Bang,
+            Some(TokenType::Minus) => UnaryOp::Minus,
+            Some(TokenType::Tilde) => UnaryOp::Tilde,
+            _ => return self.power(),
+        };
+        let token = self.advance()?;
+        let expr = self.nested(Self::unary)?;
+        let span = token.span.to(expr.span);
+        Ok(Expr::new(ExprKind::Unary(op, Box::new(expr)), span))
+    }
+
+    /// `**` binds tighter than a unary operator before it and groups to the
+    /// right, so `-2 ** 2` is `-(2 ** 2)` and `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+    fn power(&mut self) -> LoxResult<Expr> {
+        let base = self.call()?;
+        if self.advance_if(&TokenType::StarStar)?.is_none() {
+            return Ok(base);
+        }
+        let exponent = self.nested(Self::unary)?;
+        let span = base.span.to(exponent.span);
+        Ok(Expr::new(
+            ExprKind::Binary(Box::new(base), BinaryOp::StarStar, Box::new(exponent)),
+            span,
+        ))
+    }
+
+    fn call(&mut self) -> LoxResult<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.advance_if(&TokenType::LeftParen)?.is_some() {
+                let args = self.arguments(TokenType::RightParen)?;
+                let closing = self.consume(TokenType::RightParen)?;
+                let span = expr.span.to(closing.span);
+                expr = Expr::new(ExprKind::Call(Box::new(expr), args), span);
+            } else if self.advance_if(&TokenType::LeftBracket)?.is_some() {
+                let index = self.expression()?;
+                let closing = self.consume(TokenType::RightBracket)?;
+                let span = expr.span.to(closing.span);
+                expr = Expr::new(ExprKind::Index(Box::new(expr), Box::new(index)), span);
+            } else if self.advance_if(&TokenType::Dot)?.is_some() {
+                let name_span = self.peek()?.map_or(expr.span, |token| token.span);
+                let name = self.consume_identifier()?;
+                let span = expr.span.to(name_span);
+                expr = Expr::new(ExprKind::Get(Box::new(expr), name), span);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    /// Comma-separated expressions up to, but not consuming, `closing`.
+    fn arguments(&mut self, closing: TokenType) -> LoxResult<Vec<Expr>> {
+        let mut args = Vec::new();
+        if !self.check(&closing)? {
+            loop {
+                args.push(self.assignment()?);
+                if self.advance_if(&TokenType::Comma)?.is_none() {
+                    break;
+                }
+            }
+        }
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> LoxResult<Expr> {
+        let token = self.advance()?;
+        let literal = |lit: LoxType| Ok(Expr::new(ExprKind::Literal(lit), token.span));
+        match &token.token_type {
+            TokenType::Identifier(name) => {
+                Ok(Expr::new(ExprKind::Variable(name.clone()), token.span))
+            }
+            TokenType::Number(num) => literal(LoxType::Number(*num)),
+            TokenType::String_(str_val) => literal(LoxType::String_(str_val.clone())),
+            TokenType::False => literal(LoxType::Boolean(false)),
+            TokenType::True => literal(LoxType::Boolean(true)),
+            TokenType::Nil => literal(LoxType::Nil),
+            TokenType::LeftParen => {
+                let expr = self.expression()?;
+                let closing = self.consume(TokenType::RightParen)?;
+                Ok(Expr::new(expr.kind, token.span.to(closing.span)))
+            }
+            TokenType::LeftBracket => {
+                let elements = self.arguments(TokenType::RightBracket)?;
+                let closing = self.consume(TokenType::RightBracket)?;
+                Ok(Expr::new(
+                    ExprKind::List(elements),
+                    token.span.to(closing.span),
+                ))
+            }
+            TokenType::LeftBrace => {
+                let mut entries = Vec::new();
+                if !self.check(&TokenType::RightBrace)? {
+                    loop {
+                        let key = self.assignment()?;
+                        self.consume(TokenType::Colon)?;
+                        entries.push((key, self.assignment()?));
+                        if self.advance_if(&TokenType::Comma)?.is_none() {
+                            break;
+                        }
+                    }
+                }
+                let closing = self.consume(TokenType::RightBrace)?;
+                Ok(Expr::new(
+                    ExprKind::Map(entries),
+                    token.span.to(closing.span),
+                ))
+            }
+            TokenType::Eof => Err(ParsingError::UnexpectedEof)?,
+            _ => Err(ParsingError::UnexpectedToken(token.clone()))?,
         }
-        _ => primary(tokens),
     }
 }
 
-fn primary(tokens: &[Token]) -> LoxResult<(Expr, &[Token])> {
-    let token = tokens.first().expect("First token didn't exist");
-    match &token.token_type {
-        TokenType::Identifier(str_val) => Ok((
-            Expr::Literal(LoxType::Identifier(str_val.clone())),
-            &tokens[1..],
-        )),
-        TokenType::Number(num) => Ok((Expr::Literal(LoxType::Number(*num)), &tokens[1..])),
-        TokenType::String_(str_val) => Ok((
-            Expr::Literal(LoxType::String_(str_val.clone())),
-            &tokens[1..],
-        )),
-        TokenType::False => Ok((Expr::Literal(LoxType::Boolean(false)), &tokens[1..])),
-        TokenType::True => Ok((Expr::Literal(LoxType::Boolean(true)), &tokens[1..])),
-        TokenType::Nil => Ok((Expr::Literal(LoxType::Nil), &tokens[1..])),
-        TokenType::LeftParen => {
-            let (expr, tail) = expression(&tokens[1..])?;
-            match tail
-                .first()
-                .ok_or_else(|| ParsingError::ExpectedToken(TokenType::RightParen))?
-            {
-                Token {
-                    token_type: TokenType::RightParen,
-                    ..
-                } => Ok((expr, &tail[1..])),
-                token => Err(ParsingError::UnexpectedToken(token.clone()))?,
-            }
-        }
-        _ => Err(ParsingError::UnexpectedToken(token.clone()))?,
+#[cfg(test)]
+mod test {
+    use crate::error::LexingError;
+    use crate::lexer::Lexer;
+    use crate::printer::to_sexpr;
+    use crate::test_util::with_big_stack;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_from_lexer() {
+        let expr = Parser::new(Lexer::new("1 + 2 * 3".chars()))
+            .parse()
+            .unwrap();
+
+        assert_eq!(to_sexpr(&expr), "(+ 1 (* 2 3))");
+    }
+
+    #[test]
+    fn test_lexing_error_surfaces() {
+        match Parser::new(Lexer::new("1 + $".chars())).parse() {
+            Err(LoxError::InnerLexingError(LexingError::InvalidToken { token: '$', .. })) => {}
+            res => panic!("Expected Invalid Token, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        match Parser::new(Lexer::new("(1 +".chars())).parse() {
+            Err(LoxError::InnerParsingError(ParsingError::UnexpectedEof)) => {}
+            res => panic!("Expected Unexpected Eof, got {:?}", res),
+        }
+        match parse_tokens(&[]) {
+            Err(LoxError::InnerParsingError(ParsingError::UnexpectedEof)) => {}
+            res => panic!("Expected Unexpected Eof, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let program = Parser::new(Lexer::new("var a = 1; { print a; } a = 2;".chars()))
+            .parse_program()
+            .unwrap();
+
+        assert_eq!(program.len(), 3);
+        match &program[2].kind {
+            StmtKind::Expression(expr) => assert_eq!(to_sexpr(expr), "(= a 2)"),
+            stmt => panic!("Expected an assignment, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_braces_start_blocks_or_maps() {
+        let program = Parser::new(Lexer::new("{} print {}; { var m = {\"a\": {}}; }".chars()))
+            .parse_program()
+            .unwrap();
+
+        let kinds: Vec<_> = program.iter().map(|stmt| &stmt.kind).collect();
+        match &kinds[..] {
+            [StmtKind::Block(empty), StmtKind::Print(map), StmtKind::Block(inner)] => {
+                assert!(empty.is_empty());
+                assert_eq!(to_sexpr(map), "(map)");
+                assert!(matches!(
+                    inner[..],
+                    [Stmt {
+                        kind: StmtKind::Var(_, Some(_)),
+                        ..
+                    }]
+                ));
+            }
+            program => panic!("Expected a block, print and block, got {:?}", program),
+        }
+        assert!(Parser::new(Lexer::new("{\"a\": 1};".chars()))
+            .parse_program()
+            .is_err());
+    }
+
+    #[test]
+    fn test_program_errors() {
+        match Parser::new(Lexer::new("1 + 2 = 3;".chars())).parse_program() {
+            Err(LoxError::InnerParsingError(ParsingError::InvalidAssignmentTarget(_))) => {}
+            res => panic!("Expected Invalid Assignment Target, got {:?}", res),
+        }
+        match Parser::new(Lexer::new("print 1".chars())).parse_program() {
+            Err(LoxError::InnerParsingError(err)) => assert!(err.at_eof()),
+            res => panic!("Expected a missing semicolon, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_loop_control_errors() {
+        for source in &[
+            "break;",
+            "if (true) continue;",
+            "while (true) { fun f() { break; } }",
+            "fun f() { continue; }",
+        ] {
+            match Parser::new(Lexer::new(source.chars())).parse_program() {
+                Err(LoxError::InnerParsingError(ParsingError::OutsideLoop(_))) => {}
+                res => panic!("Expected loop control outside a loop, got {:?}", res),
+            }
+        }
+        let nested = "while (true) { if (true) { break; } }
+            for (;;) { fun f() { while (true) continue; } break; }";
+        assert!(Parser::new(Lexer::new(nested.chars()))
+            .parse_program()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_conditional_errors() {
+        match Parser::new(Lexer::new("print a ? b;".chars())).parse_program() {
+            Err(LoxError::InnerParsingError(err @ ParsingError::MissingColon { .. })) => {
+                assert_eq!(
+                    err.to_string(),
+                    "Expected ':' after the branch of '?' on line 1 but found ';' on line 1"
+                );
+                assert!(!err.at_eof());
+            }
+            res => panic!("Expected a missing colon, got {:?}", res),
+        }
+        match Parser::new(Lexer::new("a ? b".chars())).parse() {
+            Err(LoxError::InnerParsingError(err)) => assert!(err.at_eof()),
+            res => panic!("Expected a missing colon at the end, got {:?}", res),
+        }
+        // A comma needs an operand on each side
+        for source in &["print 1, ;", "print , 1;"] {
+            match Parser::new(Lexer::new(source.chars())).parse_program() {
+                Err(LoxError::InnerParsingError(ParsingError::UnexpectedToken(_))) => {}
+                res => panic!("Expected a missing operand, got {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        with_big_stack(|| {
+            let deep = |open: &str, close: &str, depth: usize| {
+                format!("{}print 1;{}", open.repeat(depth), close.repeat(depth))
+            };
+            for source in &[
+                format!("print {}1{};", "(".repeat(20_000), ")".repeat(20_000)),
+                format!("print {}1;", "-".repeat(MAX_NESTING)),
+                format!("print {}1;", "2 ** ".repeat(MAX_NESTING)),
+                deep("{", "}", MAX_NESTING),
+                deep("if (true) ", "", MAX_NESTING),
+            ] {
+                match Parser::new(Lexer::new(source.chars())).parse_program() {
+                    Err(LoxError::InnerParsingError(err @ ParsingError::TooDeep(_))) => {
+                        assert!(!err.at_eof())
+                    }
+                    res => panic!("Expected nesting that is too deep, got {:?}", res),
+                }
+            }
+            let shallow = deep("{", "}", 100) + &deep("while (true) ", "", 100);
+            assert!(Parser::new(Lexer::new(shallow.chars()))
+                .parse_program()
+                .is_ok());
+        });
+    }
+
+    #[test]
+    fn test_recovering_reports_every_error() {
+        let (program, errors) = Parser::new(Lexer::new(
+            "print (1;\nvar = 2;\nprint $;\nprint 3;".chars(),
+        ))
+        .parse_program_recovering();
+
+        assert_eq!(program.len(), 1);
+        assert_eq!(errors.len(), 3);
+        match &errors[2] {
+            LoxError::InnerLexingError(LexingError::InvalidToken { token: '$', .. }) => {}
+            err => panic!("Expected Invalid Token, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_functions_and_calls() {
+        let program = Parser::new(Lexer::new(
+            "fun add(a, b) { return a + b; } add(1, 2)(3);".chars(),
+        ))
+        .parse_program()
+        .unwrap();
+
+        match &program[0].kind {
+            StmtKind::Function(decl) => {
+                assert_eq!(decl.name, "add");
+                assert_eq!(decl.params, vec!["a", "b"]);
+            }
+            stmt => panic!("Expected a function, got {:?}", stmt),
+        }
+        match &program[1].kind {
+            StmtKind::Expression(expr) => assert_eq!(to_sexpr(expr), "(call (call add 1 2) 3)"),
+            stmt => panic!("Expected a call, got {:?}", stmt),
+        }
+        match Parser::new(Lexer::new("return 1;".chars())).parse_program() {
+            Err(LoxError::InnerParsingError(ParsingError::ReturnOutsideFunction(_))) => {}
+            res => panic!("Expected Return Outside Function, got {:?}", res),
+        }
     }
 }
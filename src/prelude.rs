@@ -0,0 +1,223 @@
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{LoxError, LoxResult};
+use crate::lexer::scan_tokens;
+use crate::math;
+use crate::native::{wrong_type, Arity, Context, NativeFunction};
+use crate::stack::Frame;
+use crate::system;
+use crate::token::{Span, TokenType};
+use crate::types::LoxType;
+
+/// Binds the builtin functions every program starts with into `globals`.
+pub fn install(globals: &Frame) {
+    define(globals, "clock", Arity::Fixed(0), clock);
+    define(globals, "str", Arity::Fixed(1), |_, args| {
+        Ok(LoxType::String_(args[0].to_string()))
+    });
+    define(globals, "num", Arity::Fixed(1), num);
+    define(globals, "type", Arity::Fixed(1), |_, args| {
+        Ok(LoxType::String_(args[0].type_name().into()))
+    });
+    define(globals, "len", Arity::Fixed(1), len);
+    define(globals, "input", Arity::Fixed(0), |ctx, _| {
+        Ok(ctx.read_line()?.map_or(LoxType::Nil, LoxType::String_))
+    });
+    define(globals, "exit", Arity::Fixed(1), exit);
+    math::install(globals);
+    system::install(globals);
+}
+
+pub fn define(
+    globals: &Frame,
+    name: &str,
+    arity: Arity,
+    function: impl Fn(&mut Context, &[LoxType]) -> LoxResult<LoxType> + 'static,
+) {
+    let native = NativeFunction::new(name, arity, function);
+    globals.define(name, LoxType::NativeFunction(Rc::new(native)));
+}
+
+/// Seconds since the Unix epoch.
+fn clock(_: &mut Context, _: &[LoxType]) -> LoxResult<LoxType> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System clock is before the Unix epoch");
+    Ok(LoxType::Number(now.as_secs_f64()))
+}
+
+/// Parses a number out of a string, giving `nil` if it isn't one.
+fn num(ctx: &mut Context, args: &[LoxType]) -> LoxResult<LoxType> {
+    match &args[0] {
+        LoxType::Number(num) => Ok(LoxType::Number(*num)),
+        LoxType::String_(s) => Ok(parse_number(s.trim()).map_or(LoxType::Nil, LoxType::Number)),
+        other => Err(wrong_type(ctx, "num", "a string", other)),
+    }
+}
+
+/// The value of `s` written as a Lox number literal, such as `1_000` or
+/// `0x10`, optionally with a sign.
+fn parse_number(s: &str) -> Option<f64> {
+    let (sign, literal) = match s.strip_prefix('-') {
+        Some(literal) => (-1.0, literal),
+        None => (1.0, s.strip_prefix('+').unwrap_or(s)),
+    };
+    // The literal must be a single token, followed only by the end of input
+    let tokens = scan_tokens(literal).ok()?;
+    let whole = tokens.len() == 2 && tokens[0].span == Span::new(0, literal.len());
+    match tokens[0].token_type {
+        TokenType::Number(num) if whole && num.is_finite() => Some(sign * num),
+        _ => None,
+    }
+}
+
+/// Length of a string in characters, or the number of elements in a list or
+/// entries in a map.
+fn len(ctx: &mut Context, args: &[LoxType]) -> LoxResult<LoxType> {
+    match &args[0] {
+        LoxType::String_(s) => Ok(LoxType::Number(s.chars().count() as f64)),
+        LoxType::List(list) => Ok(LoxType::Number(list.borrow().len() as f64)),
+        LoxType::Map(map) => Ok(LoxType::Number(map.borrow().len() as f64)),
+        other => Err(wrong_type(ctx, "len", "a string, list or map", other)),
+    }
+}
+
+/// Stops the program with an integer status between 0 and 255.
+fn exit(ctx: &mut Context, args: &[LoxType]) -> LoxResult<LoxType> {
+    match args[0] {
+        LoxType::Number(code) if code.fract() == 0.0 && (0.0..=255.0).contains(&code) => {
+            Err(LoxError::Exit(code as i32))
+        }
+        LoxType::Number(code) => Err(ctx.error(format!(
+            "exit() takes an integer from 0 to 255 but got {}.",
+            code
+        ))),
+        ref other => Err(wrong_type(ctx, "exit", "a number", other)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::{assert_runtime_errors, eval};
+    use crate::Lox;
+
+    use super::*;
+
+    fn string(s: &str) -> LoxType {
+        LoxType::String_(s.into())
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut lox = Lox::new();
+
+        assert_eq!(eval(&mut lox, "str(1.5)").unwrap(), string("1.5"));
+        assert_eq!(eval(&mut lox, "str(nil)").unwrap(), string("nil"));
+        assert_eq!(
+            eval(&mut lox, "num(\" 42 \")").unwrap(),
+            LoxType::Number(42.0)
+        );
+        assert_eq!(
+            eval(&mut lox, "num(\"-1e3\")").unwrap(),
+            LoxType::Number(-1000.0)
+        );
+        for (source, num) in &[
+            ("0x10", 16.0),
+            ("-0b101", -5.0),
+            ("1_000", 1000.0),
+            ("+2.5", 2.5),
+            ("1E+2", 100.0),
+        ] {
+            assert_eq!(
+                eval(&mut lox, &format!("num(\"{}\")", source)).unwrap(),
+                LoxType::Number(*num),
+                "{}",
+                source
+            );
+        }
+        for source in &[
+            "abc", "inf", "NaN", "", "-", "1_", "1__0", "0x", "1.", ".5", "1 2", "- 1", "--1",
+            "1 // one", "(1)", "1e999",
+        ] {
+            assert_eq!(
+                eval(&mut lox, &format!("num(\"{}\")", source)).unwrap(),
+                LoxType::Nil,
+                "{}",
+                source
+            );
+        }
+        assert_eq!(
+            eval(&mut lox, "len(\"héllo\")").unwrap(),
+            LoxType::Number(5.0)
+        );
+    }
+
+    #[test]
+    fn test_type() {
+        let mut lox = Lox::new();
+
+        for (source, expected) in &[
+            ("type(1)", "number"),
+            ("type(\"\")", "string"),
+            ("type(true)", "boolean"),
+            ("type(nil)", "nil"),
+            ("type(clock)", "function"),
+        ] {
+            assert_eq!(eval(&mut lox, source).unwrap(), string(expected));
+        }
+        match eval(&mut lox, "clock()").unwrap() {
+            LoxType::Number(secs) => assert!(secs > 0.0),
+            value => panic!("Expected a number, got {:?}", value),
+        }
+    }
+
+    #[test]
+    fn test_builtins_can_be_shadowed() {
+        let mut lox = Lox::new();
+        lox.run_str("var str = 1;").unwrap();
+
+        assert_eq!(eval(&mut lox, "str").unwrap(), LoxType::Number(1.0));
+        assert_eq!(lox.globals().len(), 1);
+        lox.reset();
+        assert_eq!(eval(&mut lox, "str(1)").unwrap(), string("1"));
+    }
+
+    #[test]
+    fn test_input() {
+        let mut lox = Lox::new();
+        lox.set_input("first\r\nsecond".as_bytes());
+
+        assert_eq!(eval(&mut lox, "input()").unwrap(), string("first"));
+        assert_eq!(eval(&mut lox, "input()").unwrap(), string("second"));
+        assert_eq!(eval(&mut lox, "input()").unwrap(), LoxType::Nil);
+    }
+
+    #[test]
+    fn test_exit() {
+        let mut lox = Lox::new();
+
+        match lox.run_str("exit(3); print 1;") {
+            Err(LoxError::Exit(3)) => {}
+            res => panic!("Expected Exit(3), got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_argument_validation() {
+        let mut lox = Lox::new();
+
+        assert_runtime_errors(
+            &mut lox,
+            &[
+                "len(1)",
+                "num(nil)",
+                "exit(1.5)",
+                "exit(256)",
+                "exit(\"1\")",
+                "clock(1)",
+                "str()",
+            ],
+        );
+    }
+}
@@ -0,0 +1,706 @@
+use std::fmt::Write;
+
+use crate::ast::{Expr, ExprKind, Stmt, StmtKind};
+use crate::error::LoxResult;
+use crate::parser::{parse_program, parse_tokens};
+use crate::token::{Span, Token, TokenType};
+use crate::types::LoxType;
+
+/// Renders an expression in a canonical parenthesized form, e.g. `(+ 1 (* 2 3))`.
+pub fn to_sexpr(expr: &Expr) -> String {
+    let mut out = String::new();
+    write_sexpr(&mut out, expr);
+    out
+}
+
+/// Renders an expression as a single-line JSON document. Every node carries its
+/// `span` as byte offsets into the source text.
+pub fn to_json(expr: &Expr) -> String {
+    let mut out = String::new();
+    write_json(&mut out, expr);
+    out
+}
+
+/// Renders a statement in the same form as `to_sexpr`, e.g. `(print (+ 1 2))`.
+pub fn stmt_to_sexpr(stmt: &Stmt) -> String {
+    let mut out = String::new();
+    write_stmt_sexpr(&mut out, stmt);
+    out
+}
+
+/// Renders a statement as a single-line JSON document, with a `span` on every
+/// statement and expression as in `to_json`.
+pub fn stmt_to_json(stmt: &Stmt) -> String {
+    let mut out = String::new();
+    write_stmt_json(&mut out, stmt);
+    out
+}
+
+/// Renders the AST of `tokens` with `to_sexpr`, or `to_json` if `json` is set,
+/// as a single line when they form a lone expression and otherwise as one line
+/// per top-level statement.
+pub fn ast_lines(tokens: &[Token], json: bool) -> LoxResult<Vec<String>> {
+    if let Ok(expr) = parse_tokens(tokens) {
+        let render = if json { to_json } else { to_sexpr };
+        return Ok(vec![render(&expr)]);
+    }
+    let render = if json { stmt_to_json } else { stmt_to_sexpr };
+    Ok(parse_program(tokens)?.iter().map(render).collect())
+}
+
+/// Renders a token as `line:col KIND lexeme`, followed by the literal payload for
+/// numbers, strings and identifiers. `line:col` is 1-based, matching the line
+/// numbers in error messages and editors.
+pub fn token_to_text(token: &Token) -> String {
+    let mut out = format!("{}:{} {}", token.line, token.col, token.token_type.name());
+    if !token.lexeme.is_empty() {
+        write!(out, " {}", token.lexeme.escape_debug()).unwrap();
+    }
+    if let Some(literal) = token_literal(&token.token_type) {
+        out.push(' ');
+        write_json_value(&mut out, &literal);
+    }
+    out
+}
+
+/// Renders a token as a single-line JSON object.
+pub fn token_to_json(token: &Token) -> String {
+    let mut out = format!(
+        r#"{{"line":{},"col":{},"kind":"{}","lexeme":"#,
+        token.line,
+        token.col,
+        token.token_type.name()
+    );
+    write_json_str(&mut out, &token.lexeme);
+    if let Some(literal) = token_literal(&token.token_type) {
+        out.push_str(r#","literal":"#);
+        write_json_value(&mut out, &literal);
+    }
+    out.push_str(r#","span":"#);
+    write_json_span(&mut out, token.span);
+    out.push('}');
+    out
+}
+
+fn token_literal(token_type: &TokenType) -> Option<LoxType> {
+    match token_type {
+        TokenType::Number(num) => Some(LoxType::Number(*num)),
+        TokenType::String_(s) => Some(LoxType::String_(s.clone())),
+        TokenType::Identifier(name) => Some(LoxType::String_(name.clone())),
+        _ => None,
+    }
+}
+
+/// Renders a single value the way it appears in `to_sexpr` output.
+pub fn value_to_sexpr(value: &LoxType) -> String {
+    let mut out = String::new();
+    write_literal(&mut out, value);
+    out
+}
+
+fn write_literal(out: &mut String, value: &LoxType) {
+    match value {
+        LoxType::String_(s) => write_json_str(out, s),
+        LoxType::Number(num) => write!(out, "{}", num).unwrap(),
+        LoxType::Boolean(b) => write!(out, "{}", b).unwrap(),
+        LoxType::Nil => out.push_str("nil"),
+        LoxType::Function(_)
+        | LoxType::NativeFunction(_)
+        | LoxType::NativeObject(_)
+        | LoxType::List(_)
+        | LoxType::Map(_) => write!(out, "{}", value).unwrap(),
+    }
+}
+
+fn write_sexpr(out: &mut String, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Literal(lit) => write_literal(out, lit),
+        ExprKind::Variable(name) => out.push_str(name),
+        ExprKind::Assign(name, value) => {
+            write!(out, "(= {} ", name).unwrap();
+            write_sexpr(out, value);
+            out.push(')');
+        }
+        ExprKind::Logical(left, op, right) => {
+            write!(out, "({} ", op).unwrap();
+            write_sexpr(out, left);
+            out.push(' ');
+            write_sexpr(out, right);
+            out.push(')');
+        }
+        ExprKind::Conditional(condition, then, otherwise) => {
+            out.push_str("(?: ");
+            write_sexpr(out, condition);
+            out.push(' ');
+            write_sexpr(out, then);
+            out.push(' ');
+            write_sexpr(out, otherwise);
+            out.push(')');
+        }
+        ExprKind::Get(object, name) => {
+            out.push_str("(. ");
+            write_sexpr(out, object);
+            write!(out, " {})", name).unwrap();
+        }
+        ExprKind::Set(object, name, value) => {
+            out.push_str("(= (. ");
+            write_sexpr(out, object);
+            write!(out, " {}) ", name).unwrap();
+            write_sexpr(out, value);
+            out.push(')');
+        }
+        ExprKind::Call(callee, args) => {
+            out.push_str("(call ");
+            write_sexpr(out, callee);
+            for arg in args {
+                out.push(' ');
+                write_sexpr(out, arg);
+            }
+            out.push(')');
+        }
+        ExprKind::List(elements) => {
+            out.push_str("(list");
+            for element in elements {
+                out.push(' ');
+                write_sexpr(out, element);
+            }
+            out.push(')');
+        }
+        ExprKind::Map(entries) => {
+            out.push_str("(map");
+            for (key, value) in entries {
+                out.push_str(" (");
+                write_sexpr(out, key);
+                out.push(' ');
+                write_sexpr(out, value);
+                out.push(')');
+            }
+            out.push(')');
+        }
+        ExprKind::Index(object, index) => {
+            out.push_str("(index ");
+            write_sexpr(out, object);
+            out.push(' ');
+            write_sexpr(out, index);
+            out.push(')');
+        }
+        ExprKind::SetIndex(object, index, value) => {
+            out.push_str("(= (index ");
+            write_sexpr(out, object);
+            out.push(' ');
+            write_sexpr(out, index);
+            out.push_str(") ");
+            write_sexpr(out, value);
+            out.push(')');
+        }
+        ExprKind::Unary(op, operand) => {
+            write!(out, "({} ", op).unwrap();
+            write_sexpr(out, operand);
+            out.push(')');
+        }
+        ExprKind::Binary(left, op, right) => {
+            write!(out, "({} ", op).unwrap();
+            write_sexpr(out, left);
+            out.push(' ');
+            write_sexpr(out, right);
+            out.push(')');
+        }
+    }
+}
+
+fn write_stmt_sexpr(out: &mut String, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::Expression(expr) => {
+            out.push_str("(expr ");
+            write_sexpr(out, expr);
+        }
+        StmtKind::Print(expr) => {
+            out.push_str("(print ");
+            write_sexpr(out, expr);
+        }
+        StmtKind::Var(name, initializer) => {
+            write!(out, "(var {}", name).unwrap();
+            if let Some(initializer) = initializer {
+                out.push(' ');
+                write_sexpr(out, initializer);
+            }
+        }
+        StmtKind::Block(statements) => {
+            out.push_str("(block");
+            write_stmt_sexprs(out, statements);
+        }
+        StmtKind::If(condition, then_branch, else_branch) => {
+            out.push_str("(if ");
+            write_sexpr(out, condition);
+            out.push(' ');
+            write_stmt_sexpr(out, then_branch);
+            if let Some(else_branch) = else_branch {
+                out.push(' ');
+                write_stmt_sexpr(out, else_branch);
+            }
+        }
+        StmtKind::While(condition, body, increment) => {
+            out.push_str("(while ");
+            write_sexpr(out, condition);
+            out.push(' ');
+            write_stmt_sexpr(out, body);
+            if let Some(increment) = increment {
+                out.push(' ');
+                write_sexpr(out, increment);
+            }
+        }
+        StmtKind::Function(decl) => {
+            write!(out, "(fun {} ({})", decl.name, decl.params.join(" ")).unwrap();
+            write_stmt_sexprs(out, &decl.body);
+        }
+        StmtKind::Return(value) => {
+            out.push_str("(return");
+            if let Some(value) = value {
+                out.push(' ');
+                write_sexpr(out, value);
+            }
+        }
+        StmtKind::Break => out.push_str("(break"),
+        StmtKind::Continue => out.push_str("(continue"),
+    }
+    out.push(')');
+}
+
+fn write_stmt_sexprs(out: &mut String, statements: &[Stmt]) {
+    for stmt in statements {
+        out.push(' ');
+        write_stmt_sexpr(out, stmt);
+    }
+}
+
+fn write_stmt_json(out: &mut String, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::Expression(expr) => {
+            out.push_str(r#"{"type":"Expression","expression":"#);
+            write_json(out, expr);
+        }
+        StmtKind::Print(expr) => {
+            out.push_str(r#"{"type":"Print","expression":"#);
+            write_json(out, expr);
+        }
+        StmtKind::Var(name, initializer) => {
+            out.push_str(r#"{"type":"Var","name":"#);
+            write_json_str(out, name);
+            out.push_str(r#","initializer":"#);
+            write_json_optional(out, initializer.as_ref());
+        }
+        StmtKind::Block(statements) => {
+            out.push_str(r#"{"type":"Block","body":"#);
+            write_stmt_json_array(out, statements);
+        }
+        StmtKind::If(condition, then_branch, else_branch) => {
+            out.push_str(r#"{"type":"If","condition":"#);
+            write_json(out, condition);
+            out.push_str(r#","then":"#);
+            write_stmt_json(out, then_branch);
+            out.push_str(r#","else":"#);
+            match else_branch {
+                Some(else_branch) => write_stmt_json(out, else_branch),
+                None => out.push_str("null"),
+            }
+        }
+        StmtKind::While(condition, body, increment) => {
+            out.push_str(r#"{"type":"While","condition":"#);
+            write_json(out, condition);
+            out.push_str(r#","body":"#);
+            write_stmt_json(out, body);
+            out.push_str(r#","increment":"#);
+            write_json_optional(out, increment.as_ref());
+        }
+        StmtKind::Function(decl) => {
+            out.push_str(r#"{"type":"Function","name":"#);
+            write_json_str(out, &decl.name);
+            out.push_str(r#","params":["#);
+            for (idx, param) in decl.params.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_json_str(out, param);
+            }
+            out.push_str(r#"],"body":"#);
+            write_stmt_json_array(out, &decl.body);
+        }
+        StmtKind::Return(value) => {
+            out.push_str(r#"{"type":"Return","value":"#);
+            write_json_optional(out, value.as_ref());
+        }
+        StmtKind::Break => out.push_str(r#"{"type":"Break""#),
+        StmtKind::Continue => out.push_str(r#"{"type":"Continue""#),
+    }
+    out.push_str(r#","span":"#);
+    write_json_span(out, stmt.span);
+    out.push('}');
+}
+
+fn write_stmt_json_array(out: &mut String, statements: &[Stmt]) {
+    out.push('[');
+    for (idx, stmt) in statements.iter().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+        write_stmt_json(out, stmt);
+    }
+    out.push(']');
+}
+
+fn write_json_optional(out: &mut String, expr: Option<&Expr>) {
+    match expr {
+        Some(expr) => write_json(out, expr),
+        None => out.push_str("null"),
+    }
+}
+
+fn write_json(out: &mut String, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Variable(name) => {
+            out.push_str(r#"{"type":"Identifier","name":"#);
+            write_json_str(out, name);
+        }
+        ExprKind::Literal(lit) => {
+            out.push_str(r#"{"type":"Literal","value":"#);
+            write_json_value(out, lit);
+        }
+        ExprKind::Unary(op, operand) => {
+            out.push_str(r#"{"type":"Unary","op":"#);
+            write_json_str(out, &op.to_string());
+            out.push_str(r#","operand":"#);
+            write_json(out, operand);
+        }
+        ExprKind::Binary(left, op, right) => {
+            out.push_str(r#"{"type":"Binary","op":"#);
+            write_json_str(out, &op.to_string());
+            out.push_str(r#","left":"#);
+            write_json(out, left);
+            out.push_str(r#","right":"#);
+            write_json(out, right);
+        }
+        ExprKind::Logical(left, op, right) => {
+            out.push_str(r#"{"type":"Logical","op":"#);
+            write_json_str(out, &op.to_string());
+            out.push_str(r#","left":"#);
+            write_json(out, left);
+            out.push_str(r#","right":"#);
+            write_json(out, right);
+        }
+        ExprKind::Conditional(condition, then, otherwise) => {
+            out.push_str(r#"{"type":"Conditional","condition":"#);
+            write_json(out, condition);
+            out.push_str(r#","then":"#);
+            write_json(out, then);
+            out.push_str(r#","else":"#);
+            write_json(out, otherwise);
+        }
+        ExprKind::Get(object, name) => {
+            out.push_str(r#"{"type":"Get","object":"#);
+            write_json(out, object);
+            out.push_str(r#","name":"#);
+            write_json_str(out, name);
+        }
+        ExprKind::Set(object, name, value) => {
+            out.push_str(r#"{"type":"Set","object":"#);
+            write_json(out, object);
+            out.push_str(r#","name":"#);
+            write_json_str(out, name);
+            out.push_str(r#","value":"#);
+            write_json(out, value);
+        }
+        ExprKind::Call(callee, args) => {
+            out.push_str(r#"{"type":"Call","callee":"#);
+            write_json(out, callee);
+            out.push_str(r#","arguments":"#);
+            write_json_array(out, args);
+        }
+        ExprKind::List(elements) => {
+            out.push_str(r#"{"type":"List","elements":"#);
+            write_json_array(out, elements);
+        }
+        ExprKind::Map(entries) => {
+            out.push_str(r#"{"type":"Map","entries":["#);
+            for (idx, (key, value)) in entries.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                out.push_str(r#"{"key":"#);
+                write_json(out, key);
+                out.push_str(r#","value":"#);
+                write_json(out, value);
+                out.push('}');
+            }
+            out.push(']');
+        }
+        ExprKind::Index(object, index) => {
+            out.push_str(r#"{"type":"Index","object":"#);
+            write_json(out, object);
+            out.push_str(r#","index":"#);
+            write_json(out, index);
+        }
+        ExprKind::SetIndex(object, index, value) => {
+            out.push_str(r#"{"type":"SetIndex","object":"#);
+            write_json(out, object);
+            out.push_str(r#","index":"#);
+            write_json(out, index);
+            out.push_str(r#","value":"#);
+            write_json(out, value);
+        }
+        ExprKind::Assign(name, value) => {
+            out.push_str(r#"{"type":"Assign","name":"#);
+            write_json_str(out, name);
+            out.push_str(r#","value":"#);
+            write_json(out, value);
+        }
+    }
+    out.push_str(r#","span":"#);
+    write_json_span(out, expr.span);
+    out.push('}');
+}
+
+fn write_json_array(out: &mut String, exprs: &[Expr]) {
+    out.push('[');
+    for (idx, expr) in exprs.iter().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+        write_json(out, expr);
+    }
+    out.push(']');
+}
+
+fn write_json_span(out: &mut String, span: Span) {
+    write!(out, r#"{{"start":{},"end":{}}}"#, span.start, span.end).unwrap();
+}
+
+fn write_json_value(out: &mut String, value: &LoxType) {
+    match value {
+        LoxType::String_(s) => write_json_str(out, s),
+        LoxType::Number(num) if num.is_finite() => write!(out, "{}", num).unwrap(),
+        LoxType::Number(_) | LoxType::Nil => out.push_str("null"),
+        LoxType::Boolean(b) => write!(out, "{}", b).unwrap(),
+        LoxType::Function(_)
+        | LoxType::NativeFunction(_)
+        | LoxType::NativeObject(_)
+        | LoxType::List(_)
+        | LoxType::Map(_) => write_json_str(out, &value.to_string()),
+    }
+}
+
+fn write_json_str(out: &mut String, s: &str) {
+    out.push('"');
+    for chr in s.chars() {
+        match chr {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            chr if (chr as u32) < 0x20 => write!(out, "\\u{:04x}", chr as u32).unwrap(),
+            chr => out.push(chr),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lexer::scan_tokens;
+    use crate::parser::{parse_program, parse_tokens};
+
+    use super::*;
+
+    fn parse(text: &str) -> Expr {
+        parse_tokens(&scan_tokens(text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sexpr_precedence() {
+        assert_eq!(to_sexpr(&parse("1 + 2 * 3")), "(+ 1 (* 2 3))");
+        assert_eq!(to_sexpr(&parse("1 * 2 + 3")), "(+ (* 1 2) 3)");
+        assert_eq!(to_sexpr(&parse("(1 + 2) * 3")), "(* (+ 1 2) 3)");
+        assert_eq!(to_sexpr(&parse("1 % 2 ~/ 3")), "(~/ (% 1 2) 3)");
+        assert_eq!(
+            to_sexpr(&parse("a = b or c ? d : e ? f : g, h")),
+            "(, (= a (?: (or b c) d (?: e f g))) h)"
+        );
+        assert_eq!(
+            to_sexpr(&parse("f(a, (b, c))[d, e]")),
+            "(index (call f a (, b c)) (, d e))"
+        );
+        assert_eq!(
+            to_sexpr(&parse("a | b ^ ~c & d << 1 == e")),
+            "(== (| a (^ b (& (~ c) (<< d 1)))) e)"
+        );
+        assert_eq!(to_sexpr(&parse("-a ** b ** c")), "(- (** a (** b c)))");
+        assert_eq!(
+            to_sexpr(&parse("a.b ** -c * d")),
+            "(* (** (. a b) (- c)) d)"
+        );
+    }
+
+    #[test]
+    fn test_sexpr_left_associative() {
+        assert_eq!(to_sexpr(&parse("1 - 2 - 3")), "(- (- 1 2) 3)");
+        assert_eq!(
+            to_sexpr(&parse("1 < 2 == !false")),
+            "(== (< 1 2) (! false))"
+        );
+    }
+
+    #[test]
+    fn test_sexpr_literals() {
+        assert_eq!(to_sexpr(&parse("-1.5")), "(- 1.5)");
+        assert_eq!(to_sexpr(&parse(r#""a" == nil"#)), r#"(== "a" nil)"#);
+        assert_eq!(to_sexpr(&parse("foo")), "foo");
+    }
+
+    #[test]
+    fn test_sexpr_properties() {
+        assert_eq!(to_sexpr(&parse("a.b(1).c")), "(. (call (. a b) 1) c)");
+        assert_eq!(
+            to_sexpr(&parse("a.b.c = x = 1")),
+            "(= (. (. a b) c) (= x 1))"
+        );
+    }
+
+    #[test]
+    fn test_sexpr_lists() {
+        assert_eq!(to_sexpr(&parse("[]")), "(list)");
+        assert_eq!(
+            to_sexpr(&parse("[1, [a]][0]")),
+            "(index (list 1 (list a)) 0)"
+        );
+        assert_eq!(
+            to_sexpr(&parse("a[i][j] = 2")),
+            "(= (index (index a i) j) 2)"
+        );
+        assert_eq!(
+            to_json(&parse("[x]")),
+            concat!(
+                r#"{"type":"List","elements":["#,
+                r#"{"type":"Identifier","name":"x","span":{"start":1,"end":2}}],"#,
+                r#""span":{"start":0,"end":3}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            to_json(&parse("-(x + 1)")),
+            concat!(
+                r#"{"type":"Unary","op":"-","operand":"#,
+                r#"{"type":"Binary","op":"+","#,
+                r#""left":{"type":"Identifier","name":"x","span":{"start":2,"end":3}},"#,
+                r#""right":{"type":"Literal","value":1,"span":{"start":6,"end":7}},"#,
+                r#""span":{"start":1,"end":8}},"#,
+                r#""span":{"start":0,"end":8}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_stmt_sexpr() {
+        let program = parse_program(
+            &scan_tokens(
+                "var a; fun f(x, y) { if (x) return; else print -y; }
+                 for (var i = 0; i < 2; i = i + 1) { if (i) continue; f(i, 1); break; }",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let dump: Vec<String> = program.iter().map(stmt_to_sexpr).collect();
+        assert_eq!(
+            dump,
+            vec![
+                "(var a)",
+                "(fun f (x y) (if x (return) (print (- y))))",
+                concat!(
+                    "(block (var i 0) (while (< i 2) ",
+                    "(block (if i (continue)) (expr (call f i 1)) (break)) (= i (+ i 1))))"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stmt_json() {
+        let program =
+            parse_program(&scan_tokens("fun f(a) { return; } var x = 1;").unwrap()).unwrap();
+        assert_eq!(
+            stmt_to_json(&program[0]),
+            concat!(
+                r#"{"type":"Function","name":"f","params":["a"],"body":"#,
+                r#"[{"type":"Return","value":null,"span":{"start":11,"end":18}}],"#,
+                r#""span":{"start":0,"end":20}}"#
+            )
+        );
+        assert_eq!(
+            stmt_to_json(&program[1]),
+            concat!(
+                r#"{"type":"Var","name":"x","initializer":"#,
+                r#"{"type":"Literal","value":1,"span":{"start":29,"end":30}},"#,
+                r#""span":{"start":21,"end":31}}"#
+            )
+        );
+        // A desugared `for` spans the whole loop
+        let source = "for (var i = 0; i < 2; i = i + 1) print i;";
+        let program = parse_program(&scan_tokens(source).unwrap()).unwrap();
+        assert!(stmt_to_json(&program[0]).ends_with(r#""span":{"start":0,"end":42}}"#));
+    }
+
+    #[test]
+    fn test_ast_lines() {
+        let lines = |source: &str, json| ast_lines(&scan_tokens(source).unwrap(), json);
+
+        assert_eq!(lines("1 + 2", false).unwrap(), vec!["(+ 1 2)"]);
+        assert_eq!(
+            lines("var x = 1; print x;", false).unwrap(),
+            vec!["(var x 1)", "(print x)"]
+        );
+        assert_eq!(lines("x", true).unwrap().len(), 1);
+        assert!(lines("print", false).is_err());
+    }
+
+    #[test]
+    fn test_token_text() {
+        let tokens = scan_tokens("var x = \"a b\";\n  1.50 + x").unwrap();
+        let dump: Vec<String> = tokens.iter().map(token_to_text).collect();
+        assert_eq!(
+            dump,
+            vec![
+                "1:1 Var var",
+                "1:5 Identifier x \"x\"",
+                "1:7 Equal =",
+                "1:9 String \\\"a b\\\" \"a b\"",
+                "1:14 Semicolon ;",
+                "2:3 Number 1.50 1.5",
+                "2:8 Plus +",
+                "2:10 Identifier x \"x\"",
+                "2:11 Eof",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_json() {
+        let tokens = scan_tokens("\n(12").unwrap();
+        assert_eq!(
+            token_to_json(&tokens[0]),
+            r#"{"line":2,"col":1,"kind":"LeftParen","lexeme":"(","span":{"start":1,"end":2}}"#
+        );
+        assert_eq!(
+            token_to_json(&tokens[1]),
+            r#"{"line":2,"col":2,"kind":"Number","lexeme":"12","literal":12,"span":{"start":2,"end":4}}"#
+        );
+    }
+
+    #[test]
+    fn test_json_escapes_strings() {
+        let mut out = String::new();
+        write_json_str(&mut out, "a\"b\\c\n\u{1}");
+        assert_eq!(out, r#""a\"b\\c\n\u0001""#);
+    }
+}
@@ -0,0 +1,297 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::error::{LexingError, LoxError, LoxResult};
+use crate::lexer::{scan_tokens, Lexer};
+use crate::parser::{parse_program, parse_tokens};
+use crate::printer;
+use crate::token::TokenType;
+use crate::Lox;
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+/// Runs an interactive session on stdin until Ctrl-D. Ctrl-C discards the
+/// input entered so far.
+pub fn run(lox: &mut Lox) -> LoxResult<()> {
+    let mut editor = DefaultEditor::new().map_err(editor_error)?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // There's no history yet on the first run
+        let _ = editor.load_history(path);
+    }
+
+    let mut source = String::new();
+    let mut exit = None;
+    loop {
+        let prompt = if source.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                source.push_str(&line);
+                source.push('\n');
+                if !source.starts_with(':') && is_incomplete(&source) {
+                    continue;
+                }
+                let entry = mem::take(&mut source);
+                if entry.trim().is_empty() {
+                    continue;
+                }
+                editor
+                    .add_history_entry(entry.trim_end())
+                    .map_err(editor_error)?;
+                let output = if entry.starts_with(':') {
+                    meta_command(lox, entry.trim())
+                } else {
+                    eval_entry(lox, &entry)
+                };
+                match output {
+                    Ok(output) if output.is_empty() => {}
+                    Ok(output) => println!("{}", output),
+                    Err(LoxError::Exit(code)) => {
+                        exit = Some(code);
+                        break;
+                    }
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+            Err(ReadlineError::Interrupted) => source.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(err) => Err(editor_error(err))?,
+        }
+    }
+
+    if let Some(path) = &history {
+        editor.save_history(path).map_err(editor_error)?;
+    }
+    match exit {
+        Some(code) => Err(LoxError::Exit(code)),
+        None => Ok(()),
+    }
+}
+
+/// Line editor failures are reported as I/O errors, keeping rustyline out of
+/// `LoxError`.
+fn editor_error(err: ReadlineError) -> LoxError {
+    match err {
+        ReadlineError::Io(err) => err.into(),
+        err => io::Error::other(err).into(),
+    }
+}
+
+const HELP: &str = "\
+:tokens <code>  Show the tokens <code> lexes to
+:ast <code>     Show the parsed AST of <code>, one line per statement
+:env            Show global variables, leaving out builtins
+:load <file>    Evaluate <file> in the current session
+:reset          Discard all interpreter state
+:help           Show this message";
+
+/// Runs a colon-prefixed REPL command, returning the text to print.
+pub fn meta_command(lox: &mut Lox, line: &str) -> LoxResult<String> {
+    let (command, arg) = match line.find(char::is_whitespace) {
+        Some(idx) => (&line[..idx], line[idx..].trim()),
+        None => (line, ""),
+    };
+    Ok(match command {
+        ":tokens" => scan_tokens(arg)?
+            .iter()
+            .map(printer::token_to_text)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ":ast" => printer::ast_lines(&scan_tokens(arg)?, false)?.join("\n"),
+        ":env" => lox
+            .globals()
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, printer::value_to_sexpr(value)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ":load" => {
+            let source = fs::read_to_string(arg)?;
+            eval_entry(lox, &source)?
+        }
+        ":reset" => {
+            lox.reset();
+            String::new()
+        }
+        ":help" => HELP.into(),
+        _ => format!("Unknown command '{}'. Try :help", command),
+    })
+}
+
+/// Evaluates REPL input, returning the value of a lone expression. Anything
+/// else runs as a program and prints nothing itself.
+pub fn eval_entry(lox: &mut Lox, source: &str) -> LoxResult<String> {
+    Ok(lox
+        .eval_str(source)?
+        .map(|value| printer::value_to_sexpr(&value))
+        .unwrap_or_default())
+}
+
+/// `$LOX_HISTORY`, falling back to `~/.lox_history`.
+fn history_path() -> Option<PathBuf> {
+    env::var_os("LOX_HISTORY")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".lox_history")))
+}
+
+/// Whether `source` ends in the middle of a string, comment, bracket pair or
+/// expression, meaning the REPL should ask for another line before evaluating.
+pub fn is_incomplete(source: &str) -> bool {
+    let mut tokens = Vec::new();
+    let mut depth = 0;
+    for token in Lexer::new(source.chars()) {
+        match token {
+            Ok(token) => {
+                match token.token_type {
+                    TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => {
+                        depth += 1
+                    }
+                    TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                        depth -= 1
+                    }
+                    _ => {}
+                }
+                tokens.push(token);
+            }
+            Err(LoxError::InnerLexingError(LexingError::UnexpectedEndStringLiteral { .. }))
+            | Err(LoxError::InnerLexingError(LexingError::UnterminatedComment { .. })) => {
+                return true
+            }
+            // Let evaluation report any other error
+            Err(_) => return false,
+        }
+    }
+    if depth > 0 {
+        return true;
+    }
+    // Blank lines and lone comments have nothing to continue
+    if tokens.len() == 1 {
+        return false;
+    }
+    if parse_tokens(&tokens).is_ok() {
+        return false;
+    }
+    matches!(
+        parse_program(&tokens),
+        Err(LoxError::InnerParsingError(err)) if err.at_eof()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_complete_input() {
+        for source in &[
+            "1 + 2\n",
+            "(1)\n",
+            "\"a\nb\"\n",
+            "/* a\n */ 1\n",
+            "1 +)\n",
+            "$\n",
+            "\n",
+            "// hi\n",
+            "var x = 1;\n",
+            "print x;\n",
+        ] {
+            assert!(!is_incomplete(source), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_incomplete_input() {
+        for source in &[
+            "(1 +\n",
+            "((1)\n",
+            "1 +\n",
+            "-\n",
+            "\"abc\n",
+            "/* a\n",
+            "{\n",
+            "var x = 1\n",
+            "if (x)\n",
+        ] {
+            assert!(is_incomplete(source), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_eval_entry() {
+        let mut lox = Lox::new();
+
+        assert_eq!(eval_entry(&mut lox, "var x = 2;").unwrap(), "");
+        assert_eq!(eval_entry(&mut lox, "x * 3").unwrap(), "6");
+        assert_eq!(eval_entry(&mut lox, "x = \"a\"").unwrap(), "\"a\"");
+        assert!(eval_entry(&mut lox, "y").is_err());
+    }
+
+    #[test]
+    fn test_tokens_command() {
+        let mut lox = Lox::new();
+
+        assert_eq!(
+            meta_command(&mut lox, ":tokens 1 + x").unwrap(),
+            "1:1 Number 1 1\n1:3 Plus +\n1:5 Identifier x \"x\"\n1:6 Eof"
+        );
+    }
+
+    #[test]
+    fn test_ast_command() {
+        let mut lox = Lox::new();
+
+        assert_eq!(
+            meta_command(&mut lox, ":ast 1 + 2 * 3").unwrap(),
+            "(+ 1 (* 2 3))"
+        );
+        assert_eq!(
+            meta_command(&mut lox, ":ast var x = 1; print x;").unwrap(),
+            "(var x 1)\n(print x)"
+        );
+        assert!(meta_command(&mut lox, ":ast 1 +").is_err());
+    }
+
+    #[test]
+    fn test_load_command() {
+        let mut lox = Lox::new();
+        let path = env::temp_dir().join(format!("lox_repl_load_{}.lox", std::process::id()));
+        fs::write(&path, "(1 +\n 2) * 3\n").unwrap();
+
+        let output = meta_command(&mut lox, &format!(":load {}", path.display()));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(output.unwrap(), "9");
+        assert!(meta_command(&mut lox, ":load /nonexistent/file.lox").is_err());
+    }
+
+    #[test]
+    fn test_env_and_reset_commands() {
+        let mut lox = Lox::new();
+
+        assert_eq!(meta_command(&mut lox, ":env").unwrap(), "");
+        eval_entry(&mut lox, "var b = \"x\"; var a = 1;").unwrap();
+        assert_eq!(meta_command(&mut lox, ":env").unwrap(), "a = 1\nb = \"x\"");
+        assert_eq!(meta_command(&mut lox, ":reset").unwrap(), "");
+        assert_eq!(meta_command(&mut lox, ":env").unwrap(), "");
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let mut lox = Lox::new();
+
+        assert_eq!(
+            meta_command(&mut lox, ":frobnicate").unwrap(),
+            "Unknown command ':frobnicate'. Try :help"
+        );
+        assert!(meta_command(&mut lox, ":help").unwrap().contains(":tokens"));
+    }
+}
@@ -1,7 +1,95 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
-use crate::types::LoxType;
+use std::rc::Rc;
+
+use cons_list::ConsList;
+
+use crate::ast::FunctionDecl;
+use crate::types::{LoxFunction, LoxType};
+
+/// What a name is bound to in a frame.
+enum Slot {
+    Value(LoxType),
+    /// A function declared in this frame. Its closure is this frame and the
+    /// ones below it, so it's only built when looked up, as storing it would
+    /// make the frame own itself.
+    Function(Rc<FunctionDecl>),
+}
 
 #[derive(Default)]
 pub struct Frame {
-    locals: HashMap<String, LoxType>
+    locals: RefCell<HashMap<String, Slot>>,
+}
+
+impl Frame {
+    /// Binds `name` in this frame, shadowing any previous binding.
+    pub fn define(&self, name: &str, value: LoxType) {
+        self.locals
+            .borrow_mut()
+            .insert(name.into(), Slot::Value(value));
+    }
+
+    /// Binds a function declared in this frame under its name.
+    pub fn define_function(&self, decl: Rc<FunctionDecl>) {
+        self.locals
+            .borrow_mut()
+            .insert(decl.name.clone(), Slot::Function(decl));
+    }
+
+    /// Rebinds an existing `name`, returning false if it isn't bound here.
+    pub fn assign(&self, name: &str, value: LoxType) -> bool {
+        match self.locals.borrow_mut().get_mut(name) {
+            Some(slot) => {
+                *slot = Slot::Value(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Unbinds everything, breaking reference cycles from functions stored in
+    /// a variable of a frame they captured.
+    pub fn clear(&self) {
+        let locals = std::mem::take(&mut *self.locals.borrow_mut());
+        drop(locals);
+    }
+}
+
+/// The value of the innermost binding of `name` in `stack`.
+pub fn lookup(stack: &ConsList<Frame>, name: &str) -> Option<LoxType> {
+    let mut stack = stack.clone();
+    while let Some(frame) = stack.head() {
+        if let Some(slot) = frame.locals.borrow().get(name) {
+            return Some(value(slot, &stack));
+        }
+        stack = stack.tail();
+    }
+    None
+}
+
+/// All bindings in the top frame of `stack`, sorted by name.
+pub fn bindings(stack: &ConsList<Frame>) -> Vec<(String, LoxType)> {
+    let frame = match stack.head() {
+        Some(frame) => frame,
+        None => return Vec::new(),
+    };
+    let mut bindings: Vec<_> = frame
+        .locals
+        .borrow()
+        .iter()
+        .map(|(name, slot)| (name.clone(), value(slot, stack)))
+        .collect();
+    bindings.sort_by(|(first, _), (second, _)| first.cmp(second));
+    bindings
+}
+
+/// The value in `slot` of the top frame of `stack`.
+fn value(slot: &Slot, stack: &ConsList<Frame>) -> LoxType {
+    match slot {
+        Slot::Value(value) => value.clone(),
+        Slot::Function(decl) => LoxType::Function(Rc::new(LoxFunction {
+            decl: decl.clone(),
+            closure: stack.clone(),
+        })),
+    }
 }
@@ -0,0 +1,220 @@
+//! Methods available on string values, e.g. `"abc".upper()`. Indexes count
+//! characters (Unicode scalar values) rather than bytes.
+
+use crate::error::LoxResult;
+use crate::native::{bound_method, index_arg, string_arg, Context, Method};
+use crate::types::LoxType;
+
+/// Looks up `name` on string `receiver`, bound to it as a function value.
+pub fn method(receiver: &str, name: &str) -> Option<LoxType> {
+    let (arity, method): (usize, Method<str>) = match name {
+        "substring" => (2, substring),
+        "index_of" => (1, index_of),
+        "upper" => (0, |_, s, _| Ok(string(s.to_uppercase()))),
+        "lower" => (0, |_, s, _| Ok(string(s.to_lowercase()))),
+        "trim" => (0, |_, s, _| Ok(string(s.trim()))),
+        "char_at" => (1, char_at),
+        "replace" => (2, replace),
+        "starts_with" => (1, starts_with),
+        "split" => (1, split),
+        _ => return None,
+    };
+    Some(bound_method(receiver.to_string(), name, arity, method))
+}
+
+fn string(s: impl Into<String>) -> LoxType {
+    LoxType::String_(s.into())
+}
+
+/// Characters from `start` up to, not including, `end`.
+fn substring(ctx: &mut Context, s: &str, args: &[LoxType]) -> LoxResult<LoxType> {
+    let len = s.chars().count();
+    let start = index_arg(ctx, "substring", &args[0], len, true)?;
+    let end = index_arg(ctx, "substring", &args[1], len, true)?;
+    if start > end {
+        return Err(ctx.error(format!("substring() start {} is after end {}.", start, end)));
+    }
+    Ok(string(
+        s.chars().skip(start).take(end - start).collect::<String>(),
+    ))
+}
+
+/// Character index of the first occurrence of the argument, or -1.
+fn index_of(ctx: &mut Context, s: &str, args: &[LoxType]) -> LoxResult<LoxType> {
+    let needle = string_arg(ctx, "index_of", &args[0])?;
+    Ok(LoxType::Number(match s.find(needle) {
+        Some(byte_idx) => s[..byte_idx].chars().count() as f64,
+        None => -1.0,
+    }))
+}
+
+fn char_at(ctx: &mut Context, s: &str, args: &[LoxType]) -> LoxResult<LoxType> {
+    let idx = index_arg(ctx, "char_at", &args[0], s.chars().count(), false)?;
+    Ok(string(
+        s.chars().nth(idx).expect("Index was checked").to_string(),
+    ))
+}
+
+/// Replaces every occurrence of the first argument with the second.
+fn replace(ctx: &mut Context, s: &str, args: &[LoxType]) -> LoxResult<LoxType> {
+    let from = string_arg(ctx, "replace", &args[0])?;
+    let to = string_arg(ctx, "replace", &args[1])?;
+    if from.is_empty() {
+        return Err(ctx.error("replace() can't replace an empty string."));
+    }
+    Ok(string(s.replace(from, to)))
+}
+
+fn starts_with(ctx: &mut Context, s: &str, args: &[LoxType]) -> LoxResult<LoxType> {
+    let prefix = string_arg(ctx, "starts_with", &args[0])?;
+    Ok(LoxType::Boolean(s.starts_with(prefix)))
+}
+
+/// A list of the pieces between occurrences of the argument.
+fn split(ctx: &mut Context, s: &str, args: &[LoxType]) -> LoxResult<LoxType> {
+    let separator = string_arg(ctx, "split", &args[0])?;
+    if separator.is_empty() {
+        return Err(ctx.error("split() can't split on an empty string."));
+    }
+    Ok(LoxType::list(s.split(separator).map(string).collect()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::LoxError;
+    use crate::test_util::{assert_runtime_errors, display, eval};
+    use crate::Lox;
+
+    use super::*;
+
+    #[test]
+    fn test_concatenation() {
+        let mut lox = Lox::new();
+
+        assert_eq!(
+            eval(&mut lox, "\"foo\" + \"bar\"").unwrap(),
+            string("foobar")
+        );
+        assert_eq!(eval(&mut lox, "\"\" + \"\"").unwrap(), string(""));
+        assert!(eval(&mut lox, "\"a\" + 1").is_err());
+    }
+
+    #[test]
+    fn test_methods() {
+        let mut lox = Lox::new();
+
+        assert_eq!(
+            eval(&mut lox, "\"Hello\".upper()").unwrap(),
+            string("HELLO")
+        );
+        assert_eq!(
+            eval(&mut lox, "\"Straße\".upper()").unwrap(),
+            string("STRASSE")
+        );
+        assert_eq!(eval(&mut lox, "\"ÀB\".lower()").unwrap(), string("àb"));
+        assert_eq!(
+            eval(&mut lox, "\"  a b \\n\".trim()").unwrap(),
+            string("a b")
+        );
+        assert_eq!(
+            eval(&mut lox, "\"a-b-c\".replace(\"-\", \"+\")").unwrap(),
+            string("a+b+c")
+        );
+        assert_eq!(
+            eval(&mut lox, "\"lox\".starts_with(\"lo\")").unwrap(),
+            LoxType::Boolean(true)
+        );
+        assert_eq!(
+            eval(&mut lox, "\"lox\".starts_with(\"x\")").unwrap(),
+            LoxType::Boolean(false)
+        );
+        assert_eq!(
+            eval(&mut lox, "\"lox\".upper").unwrap().to_string(),
+            "<native fn>"
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        let mut lox = Lox::new();
+
+        assert_eq!(
+            display(&mut lox, "\"a,b,,c\".split(\",\")"),
+            r#"["a", "b", "", "c"]"#
+        );
+        assert_eq!(
+            display(&mut lox, "\",a,\".split(\",\")"),
+            r#"["", "a", ""]"#
+        );
+        assert_eq!(display(&mut lox, "\"abc\".split(\",\")"), r#"["abc"]"#);
+        assert_eq!(display(&mut lox, "\"\".split(\",\")"), r#"[""]"#);
+        assert_eq!(
+            display(&mut lox, "\"a::b:c\".split(\"::\")"),
+            r#"["a", "b:c"]"#
+        );
+        assert_eq!(display(&mut lox, "\"日→本\".split(\"→\")[1]"), "本");
+        assert_eq!(display(&mut lox, "\"a b c\".split(\" \").len()"), "3");
+    }
+
+    #[test]
+    fn test_indexes_count_characters() {
+        let mut lox = Lox::new();
+
+        // 'é' and '日' take several bytes but count as one index
+        assert_eq!(eval(&mut lox, "\"héllo\".char_at(1)").unwrap(), string("é"));
+        assert_eq!(eval(&mut lox, "\"héllo\".char_at(2)").unwrap(), string("l"));
+        assert_eq!(
+            eval(&mut lox, "\"日本語\".substring(1, 3)").unwrap(),
+            string("本語")
+        );
+        assert_eq!(
+            eval(&mut lox, "\"日本語\".substring(3, 3)").unwrap(),
+            string("")
+        );
+        assert_eq!(
+            eval(&mut lox, "\"日本語\".index_of(\"語\")").unwrap(),
+            LoxType::Number(2.0)
+        );
+        assert_eq!(
+            eval(&mut lox, "\"abc\".index_of(\"\")").unwrap(),
+            LoxType::Number(0.0)
+        );
+        assert_eq!(
+            eval(&mut lox, "\"abc\".index_of(\"d\")").unwrap(),
+            LoxType::Number(-1.0)
+        );
+        assert_eq!(
+            eval(&mut lox, "len(\"日本語\")").unwrap(),
+            LoxType::Number(3.0)
+        );
+    }
+
+    #[test]
+    fn test_bad_arguments() {
+        let mut lox = Lox::new();
+
+        assert_runtime_errors(
+            &mut lox,
+            &[
+                "\"abc\".char_at(3)",
+                "\"\".char_at(0)",
+                "\"abc\".char_at(-1)",
+                "\"abc\".char_at(0.5)",
+                "\"abc\".substring(2, 1)",
+                "\"abc\".substring(0, 4)",
+                "\"abc\".index_of(1)",
+                "\"abc\".replace(\"\", \"x\")",
+                "\"abc\".split(\"\")",
+                "\"abc\".upper(1)",
+                "\"abc\".missing()",
+            ],
+        );
+        match eval(&mut lox, "\"ab\".char_at(2)") {
+            Err(LoxError::InnerRuntimeError(err)) => assert_eq!(
+                err.message,
+                "char_at() index 2 is out of bounds for length 2."
+            ),
+            res => panic!("Expected a runtime error, got {:?}", res),
+        }
+    }
+}
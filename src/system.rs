@@ -0,0 +1,209 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::error::{LoxError, LoxResult};
+use crate::native::{string_arg, Arity, Context};
+use crate::prelude::define;
+use crate::stack::Frame;
+use crate::types::LoxType;
+
+/// Binds the filesystem and environment builtins into `globals`. They're
+/// always defined, but fail unless the host granted the matching capability,
+/// except `args()`, which only sees what the host passed with `set_args`.
+pub fn install(globals: &Frame) {
+    define(globals, "readFile", Arity::Fixed(1), |ctx, args| {
+        let path = path_arg(ctx, "readFile", &args[0])?;
+        fs::read_to_string(path)
+            .map(LoxType::String_)
+            .map_err(|err| io_error(ctx, "readFile", path, err))
+    });
+    define(globals, "writeFile", Arity::Fixed(2), |ctx, args| {
+        let path = path_arg(ctx, "writeFile", &args[0])?;
+        let contents = string_arg(ctx, "writeFile", &args[1])?;
+        fs::write(path, contents)
+            .map(|_| LoxType::Nil)
+            .map_err(|err| io_error(ctx, "writeFile", path, err))
+    });
+    define(globals, "appendFile", Arity::Fixed(2), |ctx, args| {
+        let path = path_arg(ctx, "appendFile", &args[0])?;
+        let contents = string_arg(ctx, "appendFile", &args[1])?;
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map(|_| LoxType::Nil)
+            .map_err(|err| io_error(ctx, "appendFile", path, err))
+    });
+    define(globals, "fileExists", Arity::Fixed(1), |ctx, args| {
+        let path = path_arg(ctx, "fileExists", &args[0])?;
+        Ok(LoxType::Boolean(Path::new(path).exists()))
+    });
+    define(globals, "listDir", Arity::Fixed(1), |ctx, args| {
+        let path = path_arg(ctx, "listDir", &args[0])?;
+        let mut names = fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+                    .collect::<io::Result<Vec<_>>>()
+            })
+            .map_err(|err| io_error(ctx, "listDir", path, err))?;
+        names.sort();
+        Ok(LoxType::list(
+            names.into_iter().map(LoxType::String_).collect(),
+        ))
+    });
+    define(globals, "env", Arity::Fixed(1), |ctx, args| {
+        check_env(ctx, "env")?;
+        let name = string_arg(ctx, "env", &args[0])?;
+        Ok(env::var(name).map_or(LoxType::Nil, LoxType::String_))
+    });
+    define(globals, "args", Arity::Fixed(0), |ctx, _| {
+        Ok(LoxType::list(
+            ctx.args().into_iter().map(LoxType::String_).collect(),
+        ))
+    });
+}
+
+/// What a host lets scripts reach outside the interpreter. Everything is off
+/// by default, so untrusted scripts stay sandboxed.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    /// Reading and writing files.
+    pub fs: bool,
+    /// Reading environment variables and the script's arguments.
+    pub env: bool,
+}
+
+/// A path argument, checking filesystem access is allowed first.
+fn path_arg<'v>(ctx: &Context, name: &str, arg: &'v LoxType) -> LoxResult<&'v str> {
+    if !ctx.capabilities().fs {
+        return Err(ctx.error(format!(
+            "{}() needs filesystem access, e.g. --allow-fs.",
+            name
+        )));
+    }
+    string_arg(ctx, name, arg)
+}
+
+fn check_env(ctx: &Context, name: &str) -> LoxResult<()> {
+    if !ctx.capabilities().env {
+        return Err(ctx.error(format!(
+            "{}() needs environment access, e.g. --allow-env.",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn io_error(ctx: &Context, name: &str, path: &str, err: io::Error) -> LoxError {
+    ctx.error(format!("{}() failed on '{}': {}.", name, path, err))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::{assert_runtime_errors, eval};
+    use crate::Lox;
+
+    use super::*;
+
+    #[test]
+    fn test_sandboxed_by_default() {
+        let mut lox = Lox::new();
+
+        assert_runtime_errors(
+            &mut lox,
+            &[
+                "readFile(\"Cargo.toml\")",
+                "writeFile(\"x\", \"y\")",
+                "appendFile(\"x\", \"y\")",
+                "fileExists(\"Cargo.toml\")",
+                "listDir(\".\")",
+                "env(\"HOME\")",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let path = env::temp_dir().join(format!("lox_system_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut lox = Lox::new();
+        lox.set_capabilities(Capabilities {
+            fs: true,
+            ..Capabilities::default()
+        });
+        lox.set_global("path", LoxType::String_(path.clone()));
+
+        assert_eq!(
+            eval(&mut lox, "fileExists(path)").unwrap(),
+            LoxType::Boolean(false)
+        );
+        assert_runtime_errors(&mut lox, &["readFile(path)"]);
+        lox.run_str("writeFile(path, \"a\"); appendFile(path, \"b\");")
+            .unwrap();
+        let contents = eval(&mut lox, "readFile(path)");
+        let exists = eval(&mut lox, "fileExists(path)");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(contents.unwrap(), LoxType::String_("ab".into()));
+        assert_eq!(exists.unwrap(), LoxType::Boolean(true));
+        assert_runtime_errors(
+            &mut lox,
+            &["listDir(\"/nonexistent\")", "readFile(1)", "env(\"HOME\")"],
+        );
+    }
+
+    #[test]
+    fn test_list_dir() {
+        let dir = env::temp_dir().join(format!("lox_system_dir_{}", std::process::id()));
+        fs::create_dir_all(dir.join("b")).unwrap();
+        fs::write(dir.join("c.txt"), "").unwrap();
+        fs::write(dir.join("a.lox"), "").unwrap();
+        let mut lox = Lox::new();
+        lox.set_capabilities(Capabilities {
+            fs: true,
+            ..Capabilities::default()
+        });
+        lox.set_global("dir", LoxType::String_(dir.to_str().unwrap().into()));
+
+        let names = eval(&mut lox, "listDir(dir)");
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(names.unwrap().to_string(), r#"["a.lox", "b", "c.txt"]"#);
+        assert_runtime_errors(&mut lox, &["listDir(dir)"]);
+    }
+
+    #[test]
+    fn test_env() {
+        let mut lox = Lox::new();
+        lox.set_capabilities(Capabilities {
+            env: true,
+            ..Capabilities::default()
+        });
+
+        assert_eq!(
+            eval(&mut lox, "env(\"LOX_SURELY_UNSET_VARIABLE\")").unwrap(),
+            LoxType::Nil
+        );
+        match eval(&mut lox, "env(\"PATH\")").unwrap() {
+            LoxType::String_(_) => {}
+            value => panic!("Expected PATH to be set, got {:?}", value),
+        }
+        assert_runtime_errors(&mut lox, &["fileExists(\"Cargo.toml\")"]);
+    }
+
+    #[test]
+    fn test_args() {
+        let mut lox = Lox::new();
+
+        assert_eq!(eval(&mut lox, "args()").unwrap().to_string(), "[]");
+        lox.set_args(vec!["a".into(), "b c".into()]);
+        assert_eq!(
+            eval(&mut lox, "args()").unwrap().to_string(),
+            r#"["a", "b c"]"#
+        );
+    }
+}
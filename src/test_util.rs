@@ -0,0 +1,39 @@
+//! Helpers shared by the tests of the builtins and the interpreter.
+
+use std::thread;
+
+use crate::error::{LoxError, LoxResult};
+use crate::types::LoxType;
+use crate::{Lox, STACK_SIZE};
+
+/// The value of expression `source`.
+pub fn eval(lox: &mut Lox, source: &str) -> LoxResult<LoxType> {
+    lox.eval_str(source).map(Option::unwrap)
+}
+
+/// The value of expression `source` as `print` shows it.
+pub fn display(lox: &mut Lox, source: &str) -> String {
+    eval(lox, source).unwrap().to_string()
+}
+
+/// Checks that evaluating each of `sources`, an expression or a program,
+/// fails with a runtime error.
+pub fn assert_runtime_errors(lox: &mut Lox, sources: &[&str]) {
+    for source in sources {
+        match lox.eval_str(source) {
+            Err(LoxError::InnerRuntimeError(_)) => {}
+            res => panic!("Expected a runtime error for {:?}, got {:?}", source, res),
+        }
+    }
+}
+
+/// Runs `test` on a thread with room for deep recursion, which the default
+/// test threads don't have in a debug build.
+pub fn with_big_stack(test: impl FnOnce() + Send + 'static) {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(test)
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -1,20 +1,64 @@
 use lazy_static::lazy_static;
 
 use std::collections::HashMap;
+use std::fmt;
 
+use self::TokenType::*;
+
+/// Byte range of a token or AST node in the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// Span covering everything from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+
+    /// 1-based line of `source` this span starts on.
+    pub fn line_in(self, source: &str) -> usize {
+        source
+            .get(..self.start)
+            .map_or(1, |before| before.matches('\n').count() + 1)
+    }
+}
+
+/// A lexed token. `line` and `col` are 1-based, with `col` counted in `char`s.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub line: u32,
+    pub col: u32,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, line: u32) -> Token {
+    pub fn new(token_type: TokenType, lexeme: String, line: u32, col: u32, span: Span) -> Token {
         Token {
             token_type,
             lexeme,
             line,
+            col,
+            span,
+        }
+    }
+}
+
+/// Describes a token for error messages, e.g. `'foo' on line 3`.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.token_type == TokenType::Eof {
+            write!(f, "end of input on line {}", self.line)
+        } else {
+            write!(f, "'{}' on line {}", self.lexeme, self.line)
         }
     }
 }
@@ -27,13 +71,22 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
     Plus,
     Semicolon,
+    Colon,
+    Question,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
@@ -44,6 +97,10 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    TildeSlash,
+    LessLess,
+    GreaterGreater,
 
     // Literals.
     Identifier(String),
@@ -52,7 +109,9 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
@@ -71,11 +130,81 @@ pub enum TokenType {
     Eof,
 }
 
+impl TokenType {
+    /// Name of the token kind, without any literal payload.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LeftParen => "LeftParen",
+            RightParen => "RightParen",
+            LeftBrace => "LeftBrace",
+            RightBrace => "RightBrace",
+            LeftBracket => "LeftBracket",
+            RightBracket => "RightBracket",
+            Comma => "Comma",
+            Dot => "Dot",
+            Minus => "Minus",
+            Plus => "Plus",
+            Semicolon => "Semicolon",
+            Colon => "Colon",
+            Question => "Question",
+            Slash => "Slash",
+            Star => "Star",
+            Percent => "Percent",
+            Ampersand => "Ampersand",
+            Pipe => "Pipe",
+            Caret => "Caret",
+            Tilde => "Tilde",
+            Bang => "Bang",
+            BangEqual => "BangEqual",
+            Equal => "Equal",
+            EqualEqual => "EqualEqual",
+            Greater => "Greater",
+            GreaterEqual => "GreaterEqual",
+            Less => "Less",
+            LessEqual => "LessEqual",
+            StarStar => "StarStar",
+            TildeSlash => "TildeSlash",
+            LessLess => "LessLess",
+            GreaterGreater => "GreaterGreater",
+            Identifier(_) => "Identifier",
+            String_(_) => "String",
+            Number(_) => "Number",
+            And => "And",
+            Break => "Break",
+            Class => "Class",
+            Continue => "Continue",
+            Else => "Else",
+            False => "False",
+            Fun => "Fun",
+            For => "For",
+            If => "If",
+            Nil => "Nil",
+            Or => "Or",
+            Print => "Print",
+            Return => "Return",
+            Super => "Super",
+            This => "This",
+            True => "True",
+            Var => "Var",
+            While => "While",
+            Eof => "Eof",
+        }
+    }
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 lazy_static! {
     pub static ref RESERVED_TOKENS: HashMap<&'static str, TokenType> = {
         let mut m = HashMap::new();
         m.insert("and", TokenType::And);
+        m.insert("break", TokenType::Break);
         m.insert("class", TokenType::Class);
+        m.insert("continue", TokenType::Continue);
         m.insert("else", TokenType::Else);
         m.insert("false", TokenType::False);
         m.insert("for", TokenType::For);
@@ -1,9 +1,260 @@
-#[allow(dead_code)]
-#[derive(Debug, PartialEq, Clone)]
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use cons_list::ConsList;
+
+use crate::ast::FunctionDecl;
+use crate::native::{NativeFunction, NativeObject};
+use crate::stack::Frame;
+
+#[derive(Debug, Clone)]
 pub enum LoxType {
     String_(String),
     Number(f64),
-    Identifier(String),
     Boolean(bool),
     Nil,
+    Function(Rc<LoxFunction>),
+    NativeFunction(Rc<NativeFunction>),
+    NativeObject(Rc<dyn NativeObject>),
+    /// Shared and mutable, so changes show through every reference to it.
+    List(Rc<RefCell<Vec<LoxType>>>),
+    /// Shared like lists.
+    Map(Rc<RefCell<LoxMap>>),
+}
+
+impl LoxType {
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, LoxType::Nil | LoxType::Boolean(false))
+    }
+
+    /// What `type()` returns for the value.
+    pub fn type_name(&self) -> &str {
+        match self {
+            LoxType::String_(_) => "string",
+            LoxType::Number(_) => "number",
+            LoxType::Boolean(_) => "boolean",
+            LoxType::Nil => "nil",
+            LoxType::Function(_) | LoxType::NativeFunction(_) => "function",
+            LoxType::NativeObject(object) => object.class_name(),
+            LoxType::List(_) => "list",
+            LoxType::Map(_) => "map",
+        }
+    }
+
+    pub fn list(elements: Vec<LoxType>) -> LoxType {
+        LoxType::List(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn map(map: LoxMap) -> LoxType {
+        LoxType::Map(Rc::new(RefCell::new(map)))
+    }
+
+    /// Wraps a host object so scripts can use it like a class instance.
+    pub fn native_object(object: impl NativeObject + 'static) -> LoxType {
+        LoxType::NativeObject(Rc::new(object))
+    }
+}
+
+/// Values compare by content; lists, maps, functions and objects by identity.
+impl PartialEq for LoxType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LoxType::String_(first), LoxType::String_(second)) => first == second,
+            (LoxType::Number(first), LoxType::Number(second)) => first == second,
+            (LoxType::Boolean(first), LoxType::Boolean(second)) => first == second,
+            (LoxType::Nil, LoxType::Nil) => true,
+            (LoxType::Function(first), LoxType::Function(second)) => first == second,
+            (LoxType::NativeFunction(first), LoxType::NativeFunction(second)) => {
+                Rc::ptr_eq(first, second)
+            }
+            (LoxType::NativeObject(first), LoxType::NativeObject(second)) => {
+                Rc::as_ptr(first) as *const () == Rc::as_ptr(second) as *const ()
+            }
+            (LoxType::List(first), LoxType::List(second)) => Rc::ptr_eq(first, second),
+            (LoxType::Map(first), LoxType::Map(second)) => Rc::ptr_eq(first, second),
+            _ => false,
+        }
+    }
+}
+
+/// Formats a value the way `print` shows it.
+impl fmt::Display for LoxType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoxType::String_(s) => f.write_str(s),
+            LoxType::Number(num) => write!(f, "{}", num),
+            LoxType::Boolean(b) => write!(f, "{}", b),
+            LoxType::Nil => f.write_str("nil"),
+            LoxType::Function(function) => write!(f, "<fn {}>", function.decl.name),
+            LoxType::NativeFunction(_) => f.write_str("<native fn>"),
+            LoxType::NativeObject(object) => write!(f, "{} instance", object.class_name()),
+            LoxType::List(_) | LoxType::Map(_) => fmt_nested(f, self, &mut Vec::new()),
+        }
+    }
+}
+
+/// Writes a value as it appears inside a list or map, quoting strings. A list
+/// or map that contains itself is written as `[...]` or `{...}`; `seen` holds
+/// the ones being written further out.
+fn fmt_nested(f: &mut fmt::Formatter, value: &LoxType, seen: &mut Vec<*const ()>) -> fmt::Result {
+    match value {
+        LoxType::String_(s) => write!(f, "{:?}", s),
+        LoxType::List(list) => {
+            if seen.contains(&(Rc::as_ptr(list) as *const ())) {
+                return f.write_str("[...]");
+            }
+            seen.push(Rc::as_ptr(list) as *const ());
+            f.write_str("[")?;
+            for (i, element) in list.borrow().iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                fmt_nested(f, element, seen)?;
+            }
+            seen.pop();
+            f.write_str("]")
+        }
+        LoxType::Map(map) => {
+            if seen.contains(&(Rc::as_ptr(map) as *const ())) {
+                return f.write_str("{...}");
+            }
+            seen.push(Rc::as_ptr(map) as *const ());
+            f.write_str("{")?;
+            for (i, (key, value)) in map.borrow().iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}: ", key)?;
+                fmt_nested(f, value, seen)?;
+            }
+            seen.pop();
+            f.write_str("}")
+        }
+        other => write!(f, "{}", other),
+    }
+}
+
+/// Contents of a map value, iterating in insertion order.
+#[derive(Debug, Default)]
+pub struct LoxMap {
+    entries: Vec<(MapKey, LoxType)>,
+    /// Where each key's entry is in `entries`.
+    positions: HashMap<MapKey, usize>,
+}
+
+impl LoxMap {
+    pub fn get(&self, key: &MapKey) -> Option<&LoxType> {
+        self.positions.get(key).map(|&idx| &self.entries[idx].1)
+    }
+
+    pub fn contains_key(&self, key: &MapKey) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    /// Sets the value for `key`, which keeps its place if already present.
+    pub fn insert(&mut self, key: MapKey, value: LoxType) {
+        match self.positions.get(&key) {
+            Some(&idx) => self.entries[idx].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<LoxType> {
+        let idx = self.positions.remove(key)?;
+        for position in self.positions.values_mut() {
+            if *position > idx {
+                *position -= 1;
+            }
+        }
+        Some(self.entries.remove(idx).1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&MapKey, &LoxType)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+/// A map key. Numbers are equal by value, so `0` and `-0` are the same key,
+/// and strings by content.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MapKey(Key);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Number(u64),
+    String_(String),
+}
+
+impl MapKey {
+    /// The key for `value`, or a message saying why it can't be one.
+    pub fn new(value: &LoxType) -> Result<MapKey, String> {
+        match value {
+            LoxType::Number(num) if num.is_nan() => Err("Map keys can't be NaN.".into()),
+            // Adding zero turns -0 into 0, so both have the same bits
+            LoxType::Number(num) => Ok(MapKey(Key::Number((num + 0.0).to_bits()))),
+            LoxType::String_(s) => Ok(MapKey(Key::String_(s.clone()))),
+            other => Err(format!(
+                "Map keys must be numbers or strings but got {}.",
+                other.type_name()
+            )),
+        }
+    }
+
+    pub fn to_value(&self) -> LoxType {
+        match &self.0 {
+            Key::Number(bits) => LoxType::Number(f64::from_bits(*bits)),
+            Key::String_(s) => LoxType::String_(s.clone()),
+        }
+    }
+}
+
+/// Formats the key as it appears in a map, quoting strings.
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            Key::Number(bits) => write!(f, "{}", f64::from_bits(*bits)),
+            Key::String_(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+/// A `fun` declared in Lox, closing over the scope it was declared in.
+pub struct LoxFunction {
+    pub(crate) decl: Rc<FunctionDecl>,
+    pub(crate) closure: ConsList<Frame>,
+}
+
+impl LoxFunction {
+    pub fn arity(&self) -> usize {
+        self.decl.params.len()
+    }
+}
+
+/// The same declaration closing over the same frame, as a function looked
+/// up by name is rebuilt every time.
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &Self) -> bool {
+        let frame = |function: &LoxFunction| function.closure.head().map(|frame| frame as *const _);
+        Rc::ptr_eq(&self.decl, &other.decl) && frame(self) == frame(other)
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.decl.name)
+    }
 }
@@ -0,0 +1,183 @@
+//! Runs the `lox-rs` binary, checking its output and exit status.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Runs `lox-rs` with `args`, feeding it `stdin`.
+fn lox(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox-rs"))
+        .args(args)
+        .env("LOX_HISTORY", temp_path("history"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// A path in the temp directory unique to this test process.
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("lox_cli_{}_{}", std::process::id(), name))
+}
+
+/// A temporary script, deleted when dropped.
+struct Script(PathBuf);
+
+impl Script {
+    fn new(name: &str, source: &str) -> Script {
+        let path = temp_path(name);
+        fs::write(&path, source).unwrap();
+        Script(path)
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for Script {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_run_succeeds() {
+    let script = Script::new("ok.lox", "print 1 + 2;");
+    let output = lox(&["run", script.path()], "");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn test_exit_codes() {
+    let lexing = Script::new("lexing.lox", "print $;");
+    let parsing = Script::new("parsing.lox", "print (1;");
+    let runtime = Script::new("runtime.lox", "print 1;\nprint -nil;");
+    let exit = Script::new("exit.lox", "exit(3);");
+    let missing = temp_path("missing.lox");
+    let missing = missing.to_str().unwrap();
+    let dir = env::temp_dir();
+    let dir = dir.to_str().unwrap();
+
+    for (args, code) in &[
+        (vec!["run", lexing.path()], 65),
+        (vec!["run", parsing.path()], 65),
+        (vec!["check", parsing.path()], 65),
+        (vec!["run", runtime.path()], 70),
+        (vec!["run", exit.path()], 3),
+        (vec!["run", missing], 66),
+        (vec!["check", missing], 66),
+        (vec!["tokens", missing], 66),
+        (vec!["--frobnicate"], 64),
+        (vec!["run"], 64),
+        (vec!["-e", "1", "check", lexing.path()], 64),
+        // Opening a directory works, but reading it fails
+        (vec!["tokens", dir], 74),
+    ] {
+        let output = lox(args, "");
+        assert_eq!(output.status.code(), Some(*code), "lox-rs {:?}", args);
+    }
+}
+
+#[test]
+fn test_runtime_error_report() {
+    let script = Script::new("report.lox", "print 1;\nprint -nil;");
+    let output = lox(&["run", script.path()], "");
+
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Operand must be a number.\n[line 2]\n"
+    );
+}
+
+#[test]
+fn test_positional_script() {
+    let script = Script::new("positional.lox", "print 1 + 2;");
+
+    let output = lox(&[script.path()], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), stdout(&lox(&["run", script.path()], "")));
+    // A subcommand can't also be given a script
+    assert_eq!(lox(&[script.path(), "check"], "").status.code(), Some(64));
+}
+
+#[test]
+fn test_emit_aliases() {
+    let script = Script::new("emit.lox", "var x = 1;");
+
+    for (emit, command) in &[
+        ("tokens", vec!["tokens"]),
+        ("tokens-json", vec!["tokens", "--json"]),
+        ("ast-sexpr", vec!["ast"]),
+        ("ast-json", vec!["ast", "--json"]),
+    ] {
+        let emitted = lox(&[&format!("--emit={}", emit), script.path()], "");
+        let mut args = command.clone();
+        args.push(script.path());
+        assert_eq!(emitted.status.code(), Some(0));
+        assert_eq!(stdout(&emitted), stdout(&lox(&args, "")), "--emit={}", emit);
+
+        let from_stdin = lox(&[&format!("--emit={}", emit)], "var x = 1;");
+        assert_eq!(
+            stdout(&from_stdin),
+            stdout(&emitted),
+            "--emit={} from stdin",
+            emit
+        );
+    }
+    assert_eq!(lox(&["--emit=bytecode"], "").status.code(), Some(64));
+    assert_eq!(
+        lox(&["--emit=ast-sexpr", "ast"], "").status.code(),
+        Some(64)
+    );
+}
+
+#[test]
+fn test_stdin_scripts() {
+    assert_eq!(lox(&["check", "-"], "print 1;").status.code(), Some(0));
+
+    let output = lox(&["check", "-"], "print (1;\nprint $;");
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().count(), 2);
+    assert!(
+        stderr.lines().all(|line| line.starts_with("-: ")),
+        "{}",
+        stderr
+    );
+
+    let output = lox(&["run", "-"], "print 1 + 2;");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn test_script_args() {
+    let script = Script::new("args.lox", "print args();");
+
+    for args in &[
+        vec!["run", script.path(), "a", "b c"],
+        vec![script.path(), "a", "b c"],
+    ] {
+        let output = lox(args, "");
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(stdout(&output), "[\"a\", \"b c\"]\n", "lox-rs {:?}", args);
+    }
+}